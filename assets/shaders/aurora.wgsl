@@ -3,6 +3,9 @@
     forward_io::VertexOutput,
 }
 
+// 0 during the day, 1 at night
+@group(2) @binding(0) var<uniform> night_fade: f32;
+
 // OKLab color space conversions for perceptually accurate color blending
 fn oklab_to_linear_srgb(c: vec3<f32>) -> vec3<f32> {
     let L = c.x;
@@ -288,5 +291,5 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
     // Blend aurora over the sky
     let with_aurora = mix(sky_color, aurora_color, aurora_blend_factor);
     
-    return vec4<f32>(with_aurora, intensity * 0.9 + 0.1);
+    return vec4<f32>(with_aurora, (intensity * 0.9 + 0.1) * night_fade);
 }
@@ -1,13 +1,16 @@
-use bevy::{
-    prelude::*,
-    input::keyboard::KeyCode,
-};
+use bevy::prelude::*;
 use bevy_tnua::{
     builtins::{TnuaBuiltinDash, TnuaBuiltinJumpState},
     prelude::*, TnuaAnimatingState, TnuaAnimatingStateDirective, TnuaUserControlsSystemSet};
 use std::time::Duration;
 
+use crate::input::{Action, ActionState};
 use crate::player::{Player, PlayerGltfHandle};
+use crate::weather::Weather;
+
+// Swimming movement speed relative to walking, and stamina it costs per second
+const SWIM_SPEED_MODIFIER: f32 = 0.6;
+const SWIM_STAMINA_DRAIN: f32 = 8.0;
 
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -28,7 +31,16 @@ pub enum PlayerAnimationState {
     Attacking(u8, AttackDirection), 
     Rolling,
     Walking,
-    Falling
+    Falling,
+    Swimming,
+    Dying
+}
+
+// Sent when the player starts a swing, gameplay decides what it hits
+#[derive(Event)]
+pub struct PlayerAttackEvent {
+    pub combo_stage: u8,
+    pub direction: AttackDirection,
 }
 
 // Animation state machine to handle complex transitions and interrupts
@@ -161,6 +173,8 @@ pub struct PlayerAnimationNodes {
     pub roll: AnimationNodeIndex,  
     pub walk: AnimationNodeIndex,  
     pub fall: AnimationNodeIndex,  
+    pub swim: AnimationNodeIndex,  
+    pub death: AnimationNodeIndex,  
 }
 
 // Marker component for animations that use root motion
@@ -227,6 +241,18 @@ pub fn setup_animations(
         attack2: graph.add_clip(slash_anim.clone(), 1.0, root_node),
         attack3: graph.add_clip(slash_anim.clone(), 1.0, root_node),
         fall: graph.add_clip(gltf.named_animations["fall"].clone(), 1.0, root_node),
+        // The character doesn't ship a swim clip yet, fall back to a slowed down walk
+        swim: graph.add_clip(
+            gltf.named_animations.get("swim").unwrap_or(&gltf.named_animations["walk"]).clone(),
+            1.0,
+            root_node,
+        ),
+        // Same for death, falling over is the closest we have
+        death: graph.add_clip(
+            gltf.named_animations.get("death").unwrap_or(&gltf.named_animations["fall"]).clone(),
+            1.0,
+            root_node,
+        ),
     });
 
     commands
@@ -238,18 +264,29 @@ pub fn setup_animations(
 }
 
 fn apply_controls(
-    keyboard: Res<ButtonInput<KeyCode>>, 
-    mouse_input: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
     mut query: Query<(&mut TnuaController, &mut Player, &mut AnimationStateMachine, &mut AnimationCancellation)>,
     camera_query: Query<&Transform, With<Camera3d>>,
     time: Res<Time>,
     mut attack_timer: Local<Option<Timer>>,
     mut combo_window_timer: Local<Option<Timer>>,
+    mut attack_events: EventWriter<PlayerAttackEvent>,
+    weather: Res<Weather>,
 ) {
     let Ok((mut controller, mut player, mut state_machine, mut anim_cancellation)) = query.get_single_mut() else {
         return;
     };
     
+    // Dead players don't move, keep feeding a still basis so tnua holds them in place
+    if player.is_dead {
+        controller.basis(TnuaBuiltinWalk {
+            desired_velocity: Vec3::ZERO,
+            float_height: 0.1,
+            ..Default::default()
+        });
+        return;
+    }
+    
     // Initialize timers if needed
     if attack_timer.is_none() {
         *attack_timer = Some(Timer::new(Duration::from_secs_f32(0.0), TimerMode::Once));
@@ -330,22 +367,9 @@ fn apply_controls(
     let camera_forward = Vec3::new(forward.x, 0.0, forward.z).normalize();
     let camera_right = camera_forward.cross(Vec3::Y).normalize();
     
-    // Initialize movement direction
-    let mut direction = Vec3::ZERO;
-
-    // Check each movement key and add its contribution
-    if keyboard.pressed(KeyCode::KeyW) {
-        direction += camera_forward;
-    }
-    if keyboard.pressed(KeyCode::KeyS) {
-        direction -= camera_forward;
-    }
-    if keyboard.pressed(KeyCode::KeyA) {
-        direction -= camera_right;
-    }
-    if keyboard.pressed(KeyCode::KeyD) {
-        direction += camera_right;
-    }
+    // Movement keys or the left stick, a half pushed stick walks at half speed
+    let move_axis = actions.move_axis();
+    let mut direction = camera_forward * move_axis.y + camera_right * move_axis.x;
 
     // Update player's moving state
     if player.is_attacking {
@@ -380,7 +404,7 @@ fn apply_controls(
     
     let speed_modifier = if player.exhausted {
         0.5 // Very slow when exhausted
-    } else if keyboard.pressed(KeyCode::ShiftLeft) && player.stamina > 10.0 {
+    } else if actions.pressed(Action::Sprint) && player.stamina > 10.0 {
         // Running speed when shift is pressed and enough stamina
         2.0
     } else {
@@ -388,12 +412,21 @@ fn apply_controls(
     };
     
     let base_speed = 4.0;
-    let current_speed = base_speed * speed_modifier;
+    let current_speed = if player.is_swimming {
+        // Swimming is slow and sprinting doesn't help
+        base_speed * SWIM_SPEED_MODIFIER
+    } else {
+        base_speed * speed_modifier
+    };
     
-    // Handle stamina regeneration/depletion
-    if player.is_moving {
+    // Handle stamina regeneration/depletion, rain makes it harder to catch your breath
+    let stamina_regen_rate = player.stamina_regen_rate * weather.stamina_regen_factor();
+    if player.is_swimming {
+        // Swimming drains stamina instead of regenerating it
+        player.stamina = (player.stamina - SWIM_STAMINA_DRAIN * dt).max(0.0);
+    } else if player.is_moving {
         // Only use stamina when running (shift pressed)
-        if keyboard.pressed(KeyCode::ShiftLeft) && !player.exhausted {
+        if actions.pressed(Action::Sprint) && !player.exhausted {
             // Deplete stamina only when running
             player.stamina = (player.stamina - player.stamina_use_rate * dt).max(0.0);
             
@@ -404,11 +437,11 @@ fn apply_controls(
             }
         } else if !player.exhausted {
             // When walking (not running), slowly regenerate stamina
-            player.stamina = (player.stamina + player.stamina_regen_rate * 0.2 * dt).min(player.max_stamina);
+            player.stamina = (player.stamina + stamina_regen_rate * 0.2 * dt).min(player.max_stamina);
         }
     } else if !player.exhausted {
         // Regenerate stamina faster when not moving and not exhausted
-        player.stamina = (player.stamina + player.stamina_regen_rate * dt).min(player.max_stamina);
+        player.stamina = (player.stamina + stamina_regen_rate * dt).min(player.max_stamina);
     } else {
         // Handle exhaustion recovery timer
         player.exhaustion_timer -= dt;
@@ -418,18 +451,20 @@ fn apply_controls(
         
         // Slower regeneration when exhausted
         if player.stamina < 30.0 {
-            player.stamina = (player.stamina + player.stamina_regen_rate * 0.3 * dt).min(player.max_stamina);
+            player.stamina = (player.stamina + stamina_regen_rate * 0.3 * dt).min(player.max_stamina);
         }
     }
     
     controller.basis(TnuaBuiltinWalk{
         // The `desired_velocity` determines how the character will move.
-        desired_velocity: direction.normalize_or_zero() * current_speed,
+        desired_velocity: direction.clamp_length_max(1.0) * current_speed,
         // Make the character face in the opposite direction of movement
         desired_forward: Dir3::new(forward_dir).ok(),
         // The `float_height` must be greater (even if by little) from the distance between the
         // character's center and the lowest point of its collider.
         float_height: 0.1,
+        // Wet ground makes the player slower to get going and to stop
+        acceleration: TnuaBuiltinWalk::default().acceleration * weather.traction(),
         // `TnuaBuiltinWalk` has many other fields for customizing the movement - but they have
         // sensible defaults. Refer to the `TnuaBuiltinWalk`'s documentation to learn what they do.
         ..Default::default()
@@ -437,7 +472,7 @@ fn apply_controls(
 
     // Feed the jump action every frame as long as the player holds the jump button. If the player
     // stops holding the jump button, simply stop feeding the action.
-    if keyboard.pressed(KeyCode::ControlLeft) && player.stamina >= 10.0 && !player.exhausted {
+    if actions.pressed(Action::Jump) && player.stamina >= 10.0 && !player.exhausted && !player.is_swimming {
         // Use stamina for jumping
         player.stamina = (player.stamina - 1.0).max(0.0);
         
@@ -449,7 +484,7 @@ fn apply_controls(
         });
     }
 
-    if keyboard.pressed(KeyCode::Space) && player.stamina >= 10.0 && !player.exhausted && !player.is_attacking {
+    if actions.pressed(Action::Roll) && player.stamina >= 10.0 && !player.exhausted && !player.is_attacking && !player.is_swimming {
         // Use stamina for rolling
         player.stamina = (player.stamina - 1.0).max(0.0);
         
@@ -469,13 +504,13 @@ fn apply_controls(
         });
     }
     
-    // Handle attack action with left mouse button
-    if mouse_input.just_pressed(MouseButton::Left) && player.stamina >= 15.0 && !player.exhausted {
+    // Handle attack action, left mouse button by default
+    if actions.just_pressed(Action::Attack) && player.stamina >= 15.0 && !player.exhausted && !player.is_swimming {
         let in_combo_window = state_machine.combo_window_active;
         
         if !player.is_attacking || in_combo_window || anim_cancellation.cancelable {
             // Determine attack direction based on movement keys
-            let attack_direction = determine_attack_direction(&keyboard, &camera_transform.rotation);
+            let attack_direction = determine_attack_direction(&actions, &camera_transform.rotation);
             
             // Determine combo stage
             let combo_stage = if in_combo_window {
@@ -495,6 +530,10 @@ fn apply_controls(
             
             if state_machine.try_transition(new_state, Some(&anim_cancellation)) {
                 player.is_attacking = true;
+                attack_events.send(PlayerAttackEvent {
+                    combo_stage,
+                    direction: attack_direction,
+                });
                 
                 // Use stamina for attack (costs more for later combo stages)
                 let stamina_cost = 15.0 + (combo_stage as f32 * 5.0);
@@ -529,26 +568,21 @@ fn apply_controls(
     }
 }
 
-// Helper function to determine attack direction based on keyboard input
-fn determine_attack_direction(keyboard: &ButtonInput<KeyCode>, _rotation: &Quat) -> AttackDirection {
-    let forward_pressed = keyboard.pressed(KeyCode::KeyW);
-    let backward_pressed = keyboard.pressed(KeyCode::KeyS);
-    let left_pressed = keyboard.pressed(KeyCode::KeyA);
-    let right_pressed = keyboard.pressed(KeyCode::KeyD);
+// Helper function to determine attack direction based on the movement input
+fn determine_attack_direction(actions: &ActionState, _rotation: &Quat) -> AttackDirection {
+    let move_axis = actions.move_axis();
     
-    // Determine direction based on which keys are pressed
-    if forward_pressed && !backward_pressed && !left_pressed && !right_pressed {
+    // Use whichever axis the keys or stick point along the most
+    if move_axis == Vec2::ZERO {
+        // Default to forward attack when not moving
+        // Could be enhanced to use camera/player facing instead
         AttackDirection::Forward
-    } else if backward_pressed && !forward_pressed && !left_pressed && !right_pressed {
-        AttackDirection::Backward
-    } else if left_pressed && !right_pressed && !forward_pressed && !backward_pressed {
-        AttackDirection::Left
-    } else if right_pressed && !left_pressed && !forward_pressed && !backward_pressed {
+    } else if move_axis.y.abs() >= move_axis.x.abs() {
+        if move_axis.y > 0.0 { AttackDirection::Forward } else { AttackDirection::Backward }
+    } else if move_axis.x > 0.0 {
         AttackDirection::Right
     } else {
-        // Default to forward attack when no direction keys or multiple direction keys are pressed
-        // Could be enhanced to use camera/player facing instead
-        AttackDirection::Forward
+        AttackDirection::Left
     }
 }
 
@@ -556,7 +590,7 @@ fn handle_animating(
     mut player_query: Query<(&TnuaController, &mut TnuaAnimatingState<PlayerAnimationState>, &Player, &AnimationStateMachine, &AnimationCancellation)>,
     mut animation_query: Query<(&mut AnimationPlayer, &mut AnimationTransitions)>,
     animation_nodes: Option<Res<PlayerAnimationNodes>>,
-    keyboard: Res<ButtonInput<KeyCode>>, 
+    actions: Res<ActionState>,
 ) {
     // An actual game should match the animation player and the controller. Here we cheat for
     // simplicity and use the only controller and only player.
@@ -593,7 +627,9 @@ fn handle_animating(
 
     // Use the state machine as source of truth for animation state
     // This is a major improvement over the previous implementation
-    let current_status_for_animating = if player.is_attacking {
+    let current_status_for_animating = if player.is_dead {
+        PlayerAnimationState::Dying
+    } else if player.is_attacking {
         // When attacking, use the exact combo stage and direction from the state machine
         if let PlayerAnimationState::Attacking(combo_stage, direction) = state_machine.current_state {
             PlayerAnimationState::Attacking(combo_stage, direction)
@@ -601,6 +637,8 @@ fn handle_animating(
             // Fallback - should rarely happen
             PlayerAnimationState::Attacking(0, AttackDirection::Forward)
         }
+    } else if player.is_swimming {
+        PlayerAnimationState::Swimming
     } else {
         // For non-attack states, determine based on physics state
         match controller.action_name() {
@@ -649,7 +687,7 @@ fn handle_animating(
                     // Use player state from the query
                     if player.exhausted {
                         PlayerAnimationState::Walking
-                    } else if keyboard.pressed(KeyCode::ShiftLeft) {
+                    } else if actions.pressed(Action::Sprint) {
                         PlayerAnimationState::Running
                     } else {
                         PlayerAnimationState::Walking
@@ -817,6 +855,18 @@ fn handle_animating(
                         .play(&mut animation_player, animation_nodes.roll, transition_time)
                         .set_speed(1.5);
                 }
+                PlayerAnimationState::Swimming => {
+                    transitions
+                        .play(&mut animation_player, animation_nodes.swim, common_transition)
+                        .set_speed(0.7)
+                        .repeat();
+                }
+                PlayerAnimationState::Dying => {
+                    // Played once, the last frame holds until the player respawns
+                    transitions
+                        .play(&mut animation_player, animation_nodes.death, common_transition)
+                        .set_speed(0.8);
+                }
                 PlayerAnimationState::Tpose => {
                     transitions
                         .play(&mut animation_player, animation_nodes.tpose, Duration::ZERO)
@@ -904,6 +954,7 @@ pub struct PlayerAnimationPlugin;
 impl Plugin for PlayerAnimationPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_event::<PlayerAttackEvent>()
             .add_systems(Startup, setup_animations)
             .add_systems(FixedUpdate, (
                 apply_controls.in_set(TnuaUserControlsSystemSet),
@@ -1,9 +1,8 @@
 use bevy::{
-    core_pipeline::{bloom::Bloom, experimental::taa::{TemporalAntiAliasPlugin, TemporalAntiAliasing}, motion_blur::MotionBlur, tonemapping::Tonemapping, Skybox}, input::{
-        keyboard::KeyCode, mouse::{MouseMotion, MouseWheel}
-    }, pbr::{ScreenSpaceAmbientOcclusion, ScreenSpaceAmbientOcclusionQualityLevel, VolumetricFog}, prelude::*, render::view::RenderLayers, window::PrimaryWindow
+    core_pipeline::{bloom::Bloom, experimental::taa::{TemporalAntiAliasPlugin, TemporalAntiAliasing}, motion_blur::MotionBlur, tonemapping::Tonemapping, Skybox}, input::mouse::{MouseMotion, MouseWheel}, pbr::{ScreenSpaceAmbientOcclusion, ScreenSpaceAmbientOcclusionQualityLevel, VolumetricFog}, prelude::*, render::view::RenderLayers, window::PrimaryWindow
 };
 use bevy_lunex::UiSourceCamera;
+use crate::input::{Action, ActionState, StickSettings};
 use crate::player::Player;
 
 #[derive(Component)]
@@ -124,14 +123,17 @@ fn third_person_camera(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     mut mouse_motion: EventReader<MouseMotion>,
     mut mouse_wheel: EventReader<MouseWheel>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
+    sticks: Res<StickSettings>,
     player_query: Query<&Transform, (With<Player>, Without<ThirdPersonCamera>)>,
     mut camera_query: Query<(&mut Transform, &mut ThirdPersonCamera)>,
     time: Res<Time>,
     mut exit: EventWriter<AppExit>,
+    // Seconds the camera stick has been held at full tilt
+    mut look_hold_time: Local<f32>,
 ) {
     // Handle ESC key to exit the game
-    if keyboard.just_pressed(KeyCode::Escape) {
+    if actions.just_pressed(Action::Quit) {
         exit.send(AppExit::default());
     }
     
@@ -162,6 +164,23 @@ fn third_person_camera(
                 camera_params.pitch = camera_params.pitch.clamp(0.5, 1.4);
             }
             
+            // Right stick turns the camera at a rate, speeding up while it's held at full tilt
+            let look = actions.look_axis();
+            if look.length() >= 0.95 {
+                *look_hold_time += time.delta_secs();
+            } else {
+                *look_hold_time = 0.0;
+            }
+            if look != Vec2::ZERO {
+                let ramp = (*look_hold_time / sticks.look_ramp_time).min(1.0);
+                let turn = look * sticks.look_speed * (1.0 + sticks.look_boost * ramp) * time.delta_secs();
+                let dx = if camera_params.invert_x { -turn.x } else { turn.x };
+                let dy = if camera_params.invert_y { -turn.y } else { turn.y };
+                camera_params.yaw -= dx;
+                // Pushing the stick up looks up, which is a smaller pitch
+                camera_params.pitch = (camera_params.pitch - dy).clamp(0.5, 1.4);
+            }
+            
             // Handle zoom with mouse wheel
             for event in mouse_wheel.read() {
                 camera_params.distance -= event.y * camera_params.zoom_speed;
@@ -244,7 +263,7 @@ impl Default for CameraDebugSettings {
 
 // Debug system to visualize camera raycasts - useful for tuning collision
 fn debug_camera_raycast(
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut debug_settings: ResMut<CameraDebugSettings>,
     mut gizmos: Gizmos,
     player_query: Query<&Transform, (With<Player>, Without<ThirdPersonCamera>)>,
@@ -252,7 +271,7 @@ fn debug_camera_raycast(
     camera3d_query: Query<&Transform, (With<Camera3d>, Without<Player>)>,
 ) {
     // Toggle debug visualization with F1 key
-    if keyboard.just_pressed(KeyCode::F1) {
+    if actions.just_pressed(Action::DebugCamera) {
         debug_settings.show_raycast = !debug_settings.show_raycast;
         println!("Camera debug visualization: {}", if debug_settings.show_raycast { "ON" } else { "OFF" });
         
@@ -400,12 +419,12 @@ pub struct CameraPlugin;
 
 // Just use console/terminal for debugging information
 fn display_camera_debug_info(
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     camera_params: Query<&ThirdPersonCamera>,
     debug_settings: Res<CameraDebugSettings>,
 ) {
     // Check for F1 key to print debug info once
-    if keyboard.just_pressed(KeyCode::F1) && debug_settings.show_raycast {
+    if actions.just_pressed(Action::DebugCamera) && debug_settings.show_raycast {
         if let Ok(params) = camera_params.get_single() {
             // Calculate additional debug info
             let looking_down_factor = ((params.pitch + 0.8) / 2.2).clamp(0.0, 1.0);
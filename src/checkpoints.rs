@@ -0,0 +1,250 @@
+use avian3d::prelude::{AngularVelocity, LinearVelocity};
+use bevy::prelude::*;
+
+use crate::death::SafeGround;
+use crate::interactables::{InteractEvent, Interactable};
+use crate::navigation::NavMesh;
+use crate::npcs::{handle_npc_interaction, spawn_feedback_text, LevelUpStation, STATION_LEVEL_UP_PROMPT};
+use crate::physics::{BMarker, BTrigger, LevelMarker};
+use crate::player::{FallDamage, Player, PlayerDied};
+use crate::transitions::{LevelTransition, TransitionPhase};
+use crate::triggers::TriggerEntered;
+use crate::world::LevelSelection;
+
+// Seconds between dying and the respawn fade, long enough to read the death screen
+const RESPAWN_DELAY: f32 = 3.5;
+
+pub struct CheckpointsPlugin;
+
+impl Plugin for CheckpointsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RespawnPoint>()
+            .add_event::<PlayerRested>()
+            .add_systems(Update, (
+                // After the level-up handler, so the press that rests doesn't also level up
+                rest_at_stations.after(handle_npc_interaction),
+                reach_checkpoint_volumes,
+                reset_respawnables,
+                queue_respawn,
+                respawn_player,
+                finish_respawn,
+            ).chain());
+    }
+}
+
+// Where the player comes back after dying, set by resting at a station or walking through a
+// Checkpoint trigger volume. Without one the player respawns at the PlayerStart of the level
+// they died in, or where they last stood safely when it has none.
+#[derive(Resource, Default)]
+pub struct RespawnPoint {
+    pub checkpoint: Option<Checkpoint>,
+    // Counts down after death, the respawn starts once it runs out
+    pending: Option<f32>,
+    // The player stays dead until the respawn transition has put them in place
+    respawning: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub level: String,
+    pub transform: Transform,
+}
+
+// The player rested at a station, or respawned at one
+#[derive(Event)]
+pub struct PlayerRested;
+
+// Enemies that come back whenever the player rests. Enemy code marks them as defeated
+// instead of despawning them, and resting puts them back where they started. spawn is in the
+// parent's space, see enemies.rs.
+#[derive(Component)]
+pub struct Respawnable {
+    pub spawn: Transform,
+    pub defeated: bool,
+}
+
+fn restore_player(player: &mut Player) {
+    player.health = player.max_health;
+    player.stamina = player.max_stamina;
+    player.exhausted = false;
+    player.exhaustion_timer = 0.0;
+    player.is_dead = false;
+}
+
+fn rest_at_stations(
+    mut commands: Commands,
+    mut interact_events: EventReader<InteractEvent>,
+    mut stations: Query<(&mut LevelUpStation, &mut Interactable)>,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    level: Res<LevelSelection>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut rested_events: EventWriter<PlayerRested>,
+) {
+    // Stations the player already rested at level up instead, see npcs.rs
+    let Some(station) = interact_events
+        .read()
+        .map(|event| event.entity)
+        .filter(|entity| stations.get(*entity).is_ok_and(|(station, _)| !station.rested))
+        .last()
+    else {
+        return;
+    };
+    let Ok((mut player, transform)) = player_query.get_single_mut() else {
+        return;
+    };
+    if player.health <= 0.0 {
+        return;
+    }
+    if let Ok((mut station, mut interactable)) = stations.get_mut(station) {
+        station.rested = true;
+        interactable.prompt = STATION_LEVEL_UP_PROMPT.to_string();
+    }
+
+    restore_player(&mut player);
+    // Respawn where the player stood, so they come back next to the station and not inside it
+    respawn_point.checkpoint = Some(Checkpoint {
+        level: level.name.clone(),
+        transform: *transform,
+    });
+    rested_events.send(PlayerRested);
+    info!("Rested in {} at {}", level.name, transform.translation);
+
+    spawn_feedback_text(&mut commands, "Checkpoint set", 140.0, 2.0);
+}
+
+// Checkpoint volumes only move the respawn point, unlike resting they don't heal the player
+// or bring enemies back
+fn reach_checkpoint_volumes(
+    mut commands: Commands,
+    mut entered_events: EventReader<TriggerEntered>,
+    player_query: Query<(&Player, &Transform)>,
+    level: Res<LevelSelection>,
+    mut respawn_point: ResMut<RespawnPoint>,
+) {
+    for event in entered_events.read() {
+        let BTrigger::Checkpoint { id } = &event.kind else {
+            continue;
+        };
+        let Ok((player, transform)) = player_query.get_single() else {
+            continue;
+        };
+        if player.is_dead {
+            continue;
+        }
+        respawn_point.checkpoint = Some(Checkpoint {
+            level: level.name.clone(),
+            transform: *transform,
+        });
+        info!("Reached checkpoint {} in {} at {}", id, level.name, transform.translation);
+
+        spawn_feedback_text(&mut commands, "Checkpoint set", 140.0, 2.0);
+    }
+}
+
+fn reset_respawnables(
+    mut rested_events: EventReader<PlayerRested>,
+    mut respawnables: Query<(
+        &mut Respawnable,
+        &mut Transform,
+        &mut Visibility,
+        Option<&mut LinearVelocity>,
+        Option<&mut AngularVelocity>,
+    )>,
+) {
+    if rested_events.read().count() == 0 {
+        return;
+    }
+    let mut count = 0;
+    for (mut respawnable, mut transform, mut visibility, linear_velocity, angular_velocity) in &mut respawnables {
+        respawnable.defeated = false;
+        *transform = respawnable.spawn;
+        *visibility = Visibility::Inherited;
+        if let Some(mut velocity) = linear_velocity {
+            velocity.0 = Vec3::ZERO;
+        }
+        if let Some(mut velocity) = angular_velocity {
+            velocity.0 = Vec3::ZERO;
+        }
+        count += 1;
+    }
+    if count > 0 {
+        info!("Reset {} respawnable enemies", count);
+    }
+}
+
+fn queue_respawn(
+    mut died_events: EventReader<PlayerDied>,
+    mut respawn_point: ResMut<RespawnPoint>,
+) {
+    if died_events.read().count() > 0 && respawn_point.pending.is_none() {
+        respawn_point.pending = Some(RESPAWN_DELAY);
+    }
+}
+
+// Reload the checkpoint's level and bring the player back there, see finish_respawn
+fn respawn_player(
+    time: Res<Time>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut transition: ResMut<LevelTransition>,
+    level: Res<LevelSelection>,
+    markers: Query<&LevelMarker>,
+    safe_ground: Res<SafeGround>,
+    navmesh: Res<NavMesh>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Some(remaining) = respawn_point.pending else {
+        return;
+    };
+    let remaining = remaining - time.delta_secs();
+    // Let a portal transition that was already running finish first
+    if remaining > 0.0 || transition.is_active() {
+        respawn_point.pending = Some(remaining);
+        return;
+    }
+    respawn_point.pending = None;
+    respawn_point.respawning = true;
+
+    if let Some(checkpoint) = &respawn_point.checkpoint {
+        info!("Respawning at the checkpoint in {}", checkpoint.level);
+        *transition = LevelTransition::to_transform(&checkpoint.level, checkpoint.transform);
+        return;
+    }
+    // The level is reloaded as it is, so its PlayerStart will be there again
+    if markers.iter().any(|marker| marker.0 == BMarker::PlayerStart) {
+        info!("No checkpoint rested at, respawning at the start of {}", level.name);
+        *transition = LevelTransition::to_marker(&level.name, None);
+        return;
+    }
+
+    // Without a PlayerStart, come back on the last safe ground, or on the walkable floor
+    // closest to where the player died. The level origin is where the player is spawned at first.
+    let player_transform = player_query.get_single().copied().unwrap_or_default();
+    let safe_position = safe_ground.position.filter(|_| safe_ground.level == level.name);
+    let position = safe_position
+        .or_else(|| navmesh.nearest_point(player_transform.translation))
+        .unwrap_or(Vec3::ZERO);
+    info!("No checkpoint rested at and {} has no start, respawning at {}", level.name, position);
+    *transition = LevelTransition::to_transform(
+        &level.name,
+        Transform::from_translation(position).with_rotation(player_transform.rotation),
+    );
+}
+
+// Bring the player back to life once the respawn transition has put them in place, fully restored
+fn finish_respawn(
+    mut respawn_point: ResMut<RespawnPoint>,
+    transition: Res<LevelTransition>,
+    mut player_query: Query<(&mut Player, &mut FallDamage)>,
+    mut rested_events: EventWriter<PlayerRested>,
+) {
+    if !respawn_point.respawning || !matches!(transition.phase, TransitionPhase::FadingIn | TransitionPhase::Idle) {
+        return;
+    }
+    respawn_point.respawning = false;
+
+    if let Ok((mut player, mut fall)) = player_query.get_single_mut() {
+        restore_player(&mut player);
+        fall.reset();
+    }
+    rested_events.send(PlayerRested);
+}
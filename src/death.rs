@@ -0,0 +1,301 @@
+use avian3d::prelude::{ColliderParent, RigidBody};
+use bevy::prelude::*;
+use bevy_tnua::prelude::{TnuaBuiltinWalk, TnuaController};
+
+use crate::npcs::spawn_feedback_text;
+use crate::physics::BTrigger;
+use crate::player::{Player, PlayerDied};
+use crate::transitions::LevelTransition;
+use crate::triggers::TriggerVolume;
+use crate::ui::GameUI;
+use crate::world::{LevelEntity, LevelSelection};
+
+// Seconds after dying before "YOU DIED" starts to show, so the death animation plays first
+const DEATH_SCREEN_DELAY: f32 = 0.8;
+const DEATH_SCREEN_FADE_IN: f32 = 1.2;
+// Matches the respawn fade to black, which covers the screen while it goes away
+const DEATH_SCREEN_FADE_OUT: f32 = 0.5;
+const DEATH_TEXT_COLOR: Color = Color::srgb(0.6, 0.05, 0.05);
+// How close the player has to get to the bloodstain to take their souls back
+const RETRIEVE_RADIUS: f32 = 1.2;
+const BLOODSTAIN_GLOW: f32 = 2000.0;
+const BLOODSTAIN_COLOR: Color = Color::srgb(0.8, 0.1, 0.05);
+
+pub struct DeathPlugin;
+
+impl Plugin for DeathPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bloodstain>()
+            .init_resource::<SafeGround>()
+            .add_systems(Startup, (spawn_death_screen, setup_bloodstain_assets))
+            .add_systems(Update, (
+                track_safe_ground,
+                update_death_screen,
+                drop_souls_on_death,
+                spawn_bloodstain,
+                animate_bloodstain,
+                retrieve_souls,
+            ).chain());
+    }
+}
+
+// Souls the player carried when they died. They wait where the player last stood safely until
+// the player walks back to them, dying again on the way loses them for good.
+#[derive(Resource, Default)]
+pub struct Bloodstain {
+    pub lost: Option<LostSouls>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LostSouls {
+    pub level: String,
+    pub position: Vec3,
+    pub souls: usize,
+}
+
+// Last place the player stood on solid ground outside any hazard. Dying in a kill zone or a
+// pit leaves the souls here, where the player can walk back to them.
+#[derive(Resource, Default)]
+pub struct SafeGround {
+    pub level: String,
+    pub position: Option<Vec3>,
+}
+
+// The glowing pool marking the lost souls, respawned whenever its level is loaded
+#[derive(Component)]
+pub struct BloodstainPool;
+
+// Dark band across the screen holding the "YOU DIED" text
+#[derive(Component, Default)]
+pub struct DeathScreen {
+    time_dead: f32,
+    alpha: f32,
+}
+
+#[derive(Component)]
+pub struct DeathScreenText;
+
+#[derive(Resource)]
+struct BloodstainAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn spawn_death_screen(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Px(180.0),
+            top: Val::Percent(40.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(Color::NONE),
+        // Under the transition fade, so the respawn fades it out with everything else
+        GlobalZIndex(90),
+        DeathScreen::default(),
+    )).with_children(|parent| {
+        parent.spawn((
+            Text::new("YOU DIED"),
+            TextFont {
+                font_size: 96.0,
+                ..default()
+            },
+            TextColor(Color::NONE),
+            DeathScreenText,
+        ));
+    });
+}
+
+fn setup_bloodstain_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(BloodstainAssets {
+        mesh: meshes.add(Cylinder::new(0.6, 0.02)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.3, 0.02, 0.02),
+            emissive: LinearRgba::from(BLOODSTAIN_COLOR) * 4.0,
+            perceptual_roughness: 0.2,
+            ..default()
+        }),
+    });
+}
+
+fn track_safe_ground(
+    mut safe_ground: ResMut<SafeGround>,
+    level: Res<LevelSelection>,
+    transition: Res<LevelTransition>,
+    player_query: Query<(&TnuaController, &Transform, &Player)>,
+    volumes: Query<&TriggerVolume>,
+    colliders: Query<&ColliderParent>,
+    bodies: Query<&RigidBody>,
+) {
+    if safe_ground.level != level.name {
+        safe_ground.level = level.name.clone();
+        safe_ground.position = None;
+    }
+    let Ok((controller, transform, player)) = player_query.get_single() else {
+        return;
+    };
+    if player.is_dead || player.is_swimming || transition.is_active() {
+        return;
+    }
+    let Some(ground) = controller
+        .concrete_basis::<TnuaBuiltinWalk>()
+        .and_then(|(_, basis_state)| basis_state.standing_on_entity())
+    else {
+        return;
+    };
+    // Moving platforms and loose props would carry the stain away
+    let body = colliders.get(ground).map_or(ground, |parent| parent.get());
+    if !bodies.get(body).is_ok_and(|body| body.is_static()) {
+        return;
+    }
+    let in_hazard = volumes.iter().any(|volume| {
+        volume.player_inside && matches!(volume.kind, BTrigger::KillZone | BTrigger::DamageOverTime { .. })
+    });
+    if !in_hazard {
+        safe_ground.position = Some(transform.translation);
+    }
+}
+
+fn update_death_screen(
+    time: Res<Time>,
+    player_query: Query<&Player>,
+    mut screen_query: Query<(&mut DeathScreen, &mut BackgroundColor)>,
+    mut text_query: Query<&mut TextColor, With<DeathScreenText>>,
+) {
+    let Ok((mut screen, mut background)) = screen_query.get_single_mut() else {
+        return;
+    };
+    let dead = player_query.get_single().is_ok_and(|player| player.is_dead);
+
+    if dead {
+        screen.time_dead += time.delta_secs();
+        screen.alpha = ((screen.time_dead - DEATH_SCREEN_DELAY) / DEATH_SCREEN_FADE_IN).clamp(0.0, 1.0);
+    } else {
+        screen.time_dead = 0.0;
+        screen.alpha = (screen.alpha - time.delta_secs() / DEATH_SCREEN_FADE_OUT).max(0.0);
+    }
+
+    background.0 = Color::srgba(0.0, 0.0, 0.0, 0.6 * screen.alpha);
+    if let Ok(mut text_color) = text_query.get_single_mut() {
+        text_color.0 = DEATH_TEXT_COLOR.with_alpha(screen.alpha);
+    }
+}
+
+// Leave the carried souls where the player last stood safely, losing the ones from the
+// previous death
+fn drop_souls_on_death(
+    mut commands: Commands,
+    mut died_events: EventReader<PlayerDied>,
+    mut game_ui: ResMut<GameUI>,
+    mut bloodstain: ResMut<Bloodstain>,
+    safe_ground: Res<SafeGround>,
+    level: Res<LevelSelection>,
+    pools: Query<Entity, With<BloodstainPool>>,
+) {
+    for event in died_events.read() {
+        if let Some(lost) = bloodstain.lost.take() {
+            info!("Died before reaching the bloodstain, {} souls are lost", lost.souls);
+        }
+        for pool in &pools {
+            commands.entity(pool).despawn_recursive();
+        }
+
+        let souls = std::mem::take(&mut game_ui.souls);
+        if souls == 0 {
+            continue;
+        }
+        // Without safe ground in this level yet, e.g. dying right after arriving, fall back
+        // to where the player died
+        let position = safe_ground
+            .position
+            .filter(|_| safe_ground.level == level.name)
+            .unwrap_or(event.position);
+        info!("Dropped {} souls at {}", souls, position);
+        bloodstain.lost = Some(LostSouls {
+            level: level.name.clone(),
+            position,
+            souls,
+        });
+    }
+}
+
+// The pool is a level entity, so it goes away with its level and comes back when the player
+// returns there, including the reload on respawn
+fn spawn_bloodstain(
+    mut commands: Commands,
+    assets: Res<BloodstainAssets>,
+    bloodstain: Res<Bloodstain>,
+    level: Res<LevelSelection>,
+    transition: Res<LevelTransition>,
+    pools: Query<(), With<BloodstainPool>>,
+) {
+    let Some(lost) = &bloodstain.lost else {
+        return;
+    };
+    if transition.is_active() || lost.level != level.name || !pools.is_empty() {
+        return;
+    }
+    commands.spawn((
+        Name::new(format!("Bloodstain ({})", lost.souls)),
+        Mesh3d(assets.mesh.clone()),
+        MeshMaterial3d(assets.material.clone()),
+        PointLight {
+            color: BLOODSTAIN_COLOR,
+            intensity: BLOODSTAIN_GLOW,
+            range: 3.0,
+            shadows_enabled: false,
+            ..default()
+        },
+        Transform::from_translation(lost.position + Vec3::Y * 0.05),
+        BloodstainPool,
+        LevelEntity,
+    ));
+}
+
+// Slow pulse so the pool is easy to spot from a distance
+fn animate_bloodstain(
+    time: Res<Time>,
+    mut pools: Query<&mut PointLight, With<BloodstainPool>>,
+) {
+    let wave = (time.elapsed_secs() * 2.5).sin();
+    for mut light in &mut pools {
+        light.intensity = BLOODSTAIN_GLOW * (0.7 + 0.3 * wave);
+    }
+}
+
+fn retrieve_souls(
+    mut commands: Commands,
+    mut bloodstain: ResMut<Bloodstain>,
+    mut game_ui: ResMut<GameUI>,
+    transition: Res<LevelTransition>,
+    pools: Query<(Entity, &Transform), With<BloodstainPool>>,
+    player_query: Query<(&Transform, &Player), Without<BloodstainPool>>,
+) {
+    let Ok((player_transform, player)) = player_query.get_single() else {
+        return;
+    };
+    if player.is_dead || transition.is_active() {
+        return;
+    }
+    let Some((entity, _)) = pools
+        .iter()
+        .find(|(_, pool)| pool.translation.distance(player_transform.translation) <= RETRIEVE_RADIUS)
+    else {
+        return;
+    };
+    let Some(lost) = bloodstain.lost.take() else {
+        return;
+    };
+
+    game_ui.souls += lost.souls;
+    info!("Retrieved {} souls. Total: {}", lost.souls, game_ui.souls);
+    commands.entity(entity).despawn_recursive();
+    spawn_feedback_text(&mut commands, format!("Retrieved {} souls", lost.souls), 200.0, 2.0);
+}
@@ -0,0 +1,253 @@
+use avian3d::prelude::{Collider, ColliderAabb, ColliderConstructor, LinearVelocity, RigidBody};
+use bevy::{ecs::system::EntityCommands, prelude::*, scene::SceneInstanceReady};
+use std::time::Duration;
+
+use crate::animation::PlayerAttackEvent;
+use crate::physics::{GameLayer, SpawnedByLevel};
+use crate::player::Player;
+use crate::progression::PlayerProgress;
+use crate::world::{LevelEntity, LevelScene};
+
+// Sphere in front of the player that a swing hits
+pub const ATTACK_REACH: f32 = 1.2;
+pub const ATTACK_RADIUS: f32 = 1.3;
+// Damage of the first hit, strength and later combo stages add to it
+const BASE_ATTACK_DAMAGE: f32 = 25.0;
+const DAMAGE_PER_STRENGTH: f32 = 2.0;
+const DAMAGE_PER_COMBO_STAGE: f32 = 10.0;
+// Generic chunks spawned when a destructible has no fracture scene
+const DEBRIS_CHUNKS: usize = 8;
+// Debris shrinks away during its last second
+const DEBRIS_SHRINK_TIME: f32 = 1.0;
+
+pub struct DestructiblesPlugin;
+
+impl Plugin for DestructiblesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DestructibleBroken>()
+            .add_systems(Update, (
+                damage_destructibles,
+                update_debris,
+            ));
+    }
+}
+
+// Prop that breaks into debris, added from BMeshExtras::destructible
+#[derive(Component)]
+pub struct Destructible {
+    pub health: f32,
+    pub max_health: f32,
+    pub fracture_scene: Option<Handle<Scene>>,
+    pub debris_lifetime: f32,
+}
+
+// Broken piece of a destructible, despawned when its timer runs out
+#[derive(Component)]
+pub struct Debris {
+    timer: Timer,
+    initial_scale: Vec3,
+}
+
+impl Debris {
+    fn new(lifetime: f32, initial_scale: Vec3) -> Self {
+        Self {
+            timer: Timer::from_seconds(lifetime, TimerMode::Once),
+            initial_scale,
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct DestructibleBroken {
+    pub position: Vec3,
+}
+
+// Debris goes away with the level the prop belonged to, glb props also with a hot reload
+// of their scene
+fn tag_debris(entity_commands: &mut EntityCommands, level_scene: Option<Entity>) {
+    match level_scene {
+        Some(scene) => entity_commands.insert(SpawnedByLevel(scene)),
+        None => entity_commands.insert(LevelEntity),
+    };
+}
+
+pub fn attack_damage(strength: u32, combo_stage: u8) -> f32 {
+    BASE_ATTACK_DAMAGE + strength as f32 * DAMAGE_PER_STRENGTH + combo_stage as f32 * DAMAGE_PER_COMBO_STAGE
+}
+
+// Apply player swings to destructibles in reach, and break the ones that run out of health
+fn damage_destructibles(
+    mut commands: Commands,
+    mut attack_events: EventReader<PlayerAttackEvent>,
+    player_query: Query<&Transform, With<Player>>,
+    player_progress: Res<PlayerProgress>,
+    mut destructibles: Query<(Entity, &mut Destructible, &ColliderAabb, &GlobalTransform, Option<&MeshMaterial3d<StandardMaterial>>)>,
+    parents: Query<&Parent>,
+    level_scenes: Query<(), With<LevelScene>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut broken_events: EventWriter<DestructibleBroken>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    for attack in attack_events.read() {
+        // The character model faces +Z, see apply_controls
+        let facing = *player_transform.back();
+        let hit_center = player_transform.translation + Vec3::Y + facing * ATTACK_REACH;
+        let damage = attack_damage(player_progress.strength, attack.combo_stage);
+
+        for (entity, mut destructible, aabb, global_transform, material) in &mut destructibles {
+            let closest = hit_center.clamp(aabb.min, aabb.max);
+            if closest.distance(hit_center) > ATTACK_RADIUS {
+                continue;
+            }
+            destructible.health -= damage;
+            info!(
+                "Hit destructible for {:.0} damage ({:.0}/{:.0})",
+                damage, destructible.health.max(0.0), destructible.max_health
+            );
+            if destructible.health > 0.0 {
+                continue;
+            }
+
+            let transform = global_transform.compute_transform();
+            let level_scene = parents.iter_ancestors(entity).find(|ancestor| level_scenes.contains(*ancestor));
+            match &destructible.fracture_scene {
+                Some(fracture_scene) => {
+                    // The pieces are split off into their own debris once the scene is ready
+                    let mut fracture = commands.spawn((
+                        SceneRoot(fracture_scene.clone()),
+                        transform,
+                        Debris::new(destructible.debris_lifetime, transform.scale),
+                    ));
+                    tag_debris(&mut fracture, level_scene);
+                    fracture.observe(on_fracture_spawn);
+                }
+                None => {
+                    let material = material
+                        .map(|material| material.0.clone())
+                        .unwrap_or_else(|| materials.add(Color::srgb(0.45, 0.3, 0.2)));
+                    spawn_debris_chunks(
+                        &mut commands,
+                        &mut meshes,
+                        aabb,
+                        transform.rotation,
+                        material,
+                        destructible.debris_lifetime,
+                        level_scene,
+                    );
+                }
+            }
+            commands.entity(entity).despawn_recursive();
+            broken_events.send(DestructibleBroken {
+                position: transform.translation,
+            });
+        }
+    }
+}
+
+// Turn every mesh of a fracture scene into a loose dynamic piece flying away from the center.
+// Pieces are detached from the scene root, so shrinking one doesn't drag the others along
+// and the bodies don't sit under a transform physics doesn't know about.
+fn on_fracture_spawn(
+    trigger: Trigger<SceneInstanceReady>,
+    mut commands: Commands,
+    children: Query<&Children>,
+    parents: Query<&Parent>,
+    transforms: Query<&Transform>,
+    pieces: Query<(), With<Mesh3d>>,
+    roots: Query<(&Debris, Option<&SpawnedByLevel>)>,
+) {
+    let root = trigger.entity();
+    let Ok((root_debris, spawned_by)) = roots.get(root) else {
+        return;
+    };
+    let lifetime = root_debris.timer.duration().as_secs_f32();
+    let level_scene = spawned_by.map(|spawned_by| spawned_by.0);
+    let Ok(root_transform) = transforms.get(root) else {
+        return;
+    };
+
+    for entity in children.iter_descendants(root) {
+        if !pieces.contains(entity) {
+            continue;
+        }
+        // Global transforms of a scene that was just spawned aren't propagated yet
+        let mut transform = transforms.get(entity).copied().unwrap_or_default();
+        for ancestor in parents.iter_ancestors(entity) {
+            if let Ok(ancestor_transform) = transforms.get(ancestor) {
+                transform = ancestor_transform.mul_transform(transform);
+            }
+        }
+        let outward = (transform.translation - root_transform.translation).normalize_or(Vec3::Y);
+        let mut piece = commands.entity(entity);
+        piece.remove_parent().insert((
+            transform,
+            RigidBody::Dynamic,
+            ColliderConstructor::ConvexHullFromMesh,
+            GameLayer::prop(),
+            LinearVelocity(outward * 3.0 + Vec3::Y * 2.0),
+            Debris::new(lifetime, transform.scale),
+        ));
+        tag_debris(&mut piece, level_scene);
+    }
+    commands.entity(root).despawn_recursive();
+}
+
+// Fallback when there's no fracture scene: a handful of cubes filling the prop's bounds
+fn spawn_debris_chunks(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    aabb: &ColliderAabb,
+    rotation: Quat,
+    material: Handle<StandardMaterial>,
+    lifetime: f32,
+    level_scene: Option<Entity>,
+) {
+    let center = (aabb.min + aabb.max) * 0.5;
+    let chunk_size = ((aabb.max - aabb.min) * 0.4).max(Vec3::splat(0.05));
+    let mesh = meshes.add(Cuboid::new(chunk_size.x, chunk_size.y, chunk_size.z));
+
+    for i in 0..DEBRIS_CHUNKS {
+        // Spread the chunks evenly around the center using the golden angle
+        let angle = i as f32 * 2.4;
+        let height = (i as f32 + 0.5) / DEBRIS_CHUNKS as f32 - 0.5;
+        let local_direction = Vec3::new(angle.cos(), height, angle.sin()).normalize();
+        let direction = rotation * local_direction;
+        let offset = rotation * (local_direction * chunk_size * 0.5);
+
+        let mut chunk = commands.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_translation(center + offset).with_rotation(rotation),
+            RigidBody::Dynamic,
+            Collider::cuboid(chunk_size.x, chunk_size.y, chunk_size.z),
+            GameLayer::prop(),
+            LinearVelocity(direction * 3.0 + Vec3::Y * 2.0),
+            Debris::new(lifetime, Vec3::ONE),
+        ));
+        tag_debris(&mut chunk, level_scene);
+    }
+}
+
+fn update_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut debris_query: Query<(Entity, &mut Debris, &mut Transform)>,
+) {
+    for (entity, mut debris, mut transform) in &mut debris_query {
+        debris.timer.tick(Duration::from_secs_f32(time.delta_secs()));
+
+        if debris.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let remaining = debris.timer.remaining_secs();
+        if remaining < DEBRIS_SHRINK_TIME {
+            transform.scale = debris.initial_scale * (remaining / DEBRIS_SHRINK_TIME).max(0.01);
+        }
+    }
+}
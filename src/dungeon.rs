@@ -0,0 +1,504 @@
+use avian3d::prelude::{Collider, RigidBody, Sensor};
+use bevy::prelude::*;
+use ghx_proc_gen::{
+    generator::{
+        builder::GeneratorBuilder,
+        model::{ModelCollection, ModelRotation},
+        rules::{Rules, RulesBuilder},
+        socket::{SocketCollection, SocketsCartesian2D},
+        RngMode,
+    },
+    ghx_grid::cartesian::{coordinates::Cartesian2D, grid::CartesianGrid},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::achievements::AchievementEvent;
+use crate::physics::{BMarker, BTrigger, GameLayer, LevelMarker};
+use crate::triggers::{TriggerEntered, TriggerVolume};
+use crate::world::LevelTransform;
+
+// Size of one room/corridor module
+const TILE_SIZE: f32 = 8.0;
+const WALL_HEIGHT: f32 = 4.0;
+const WALL_THICKNESS: f32 = 0.5;
+// Smaller connected layouts are thrown away and generated again with the next seed
+const MIN_DUNGEON_TILES: usize = 6;
+const MAX_GENERATION_ATTEMPTS: u64 = 10;
+// Bigger grids take too long for the solver to be generated while a level loads
+const MAX_DUNGEON_SIZE: u32 = 24;
+// What the enemy spawn slots ask for, see BMarker::EnemySpawner
+const DUNGEON_ENEMY: &str = "dungeon";
+
+pub struct DungeonPlugin;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DungeonCompleted>()
+            .add_systems(Update, complete_dungeons);
+    }
+}
+
+/// A generated dungeon placed in a level manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DungeonConfig {
+    // A new layout every run when omitted
+    pub seed: Option<u64>,
+    #[serde(default = "default_dungeon_size")]
+    pub width: u32,
+    #[serde(default = "default_dungeon_size")]
+    pub depth: u32,
+    // The entrance is on the -Z side of the dungeon. Portals with this destination id put
+    // the player there.
+    #[serde(default = "default_entrance_id")]
+    pub entrance: String,
+    #[serde(default)]
+    pub transform: LevelTransform,
+}
+
+fn default_dungeon_size() -> u32 {
+    6
+}
+
+fn default_entrance_id() -> String {
+    "dungeon_entrance".to_string()
+}
+
+impl DungeonConfig {
+    // Checks the size before it reaches the generator, which would stall or fail on it
+    pub fn validate(&self) -> Result<(), (String, String)> {
+        for (path, size) in [("width", self.width), ("depth", self.depth)] {
+            if !(2..=MAX_DUNGEON_SIZE).contains(&size) {
+                return Err((path.to_string(), format!("{path} must be between 2 and {MAX_DUNGEON_SIZE}, got {size}")));
+            }
+        }
+        if ((self.width * self.depth) as usize) < MIN_DUNGEON_TILES {
+            return Err((
+                "width".to_string(),
+                format!("a {}x{} dungeon can't fit {} connected tiles", self.width, self.depth, MIN_DUNGEON_TILES),
+            ));
+        }
+        if self.entrance.is_empty() {
+            return Err(("entrance".to_string(), "entrance id can't be empty".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// Root of a generated dungeon, the tiles are its children
+#[derive(Component)]
+pub struct Dungeon {
+    pub seed: u64,
+    pub completed: bool,
+}
+
+#[derive(Event)]
+pub struct DungeonCompleted {
+    pub dungeon: Entity,
+    pub seed: u64,
+}
+
+// The modules dungeons are assembled from, in the order they are added to the rules
+#[derive(Clone, Copy, PartialEq)]
+enum DungeonModule {
+    Solid,
+    Corridor,
+    Corner,
+    Junction,
+    Crossing,
+    Room,
+}
+
+const MODULES: [DungeonModule; 6] = [
+    DungeonModule::Solid,
+    DungeonModule::Corridor,
+    DungeonModule::Corner,
+    DungeonModule::Junction,
+    DungeonModule::Crossing,
+    DungeonModule::Room,
+];
+
+impl DungeonModule {
+    // Open sides before rotation, in +X, +Y, -X, -Y order (grid Y is world Z)
+    fn open_sides(self) -> [bool; 4] {
+        match self {
+            DungeonModule::Solid => [false, false, false, false],
+            DungeonModule::Corridor => [true, false, true, false],
+            DungeonModule::Corner => [true, true, false, false],
+            DungeonModule::Junction => [true, true, true, false],
+            DungeonModule::Crossing => [true, true, true, true],
+            DungeonModule::Room => [true, false, false, false],
+        }
+    }
+
+    fn weight(self) -> f32 {
+        match self {
+            DungeonModule::Solid => 1.5,
+            DungeonModule::Corridor | DungeonModule::Corner => 1.0,
+            DungeonModule::Junction => 0.6,
+            DungeonModule::Crossing => 0.3,
+            DungeonModule::Room => 0.8,
+        }
+    }
+}
+
+// Grid offsets of the four sides, same order as DungeonModule::open_sides
+const SIDES: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+fn opposite(side: usize) -> usize {
+    (side + 2) % 4
+}
+
+// Result of one generation, tiles are indexed x + y * width
+struct DungeonLayout {
+    width: u32,
+    depth: u32,
+    open: Vec<[bool; 4]>,
+    reachable: Vec<bool>,
+    distance: Vec<usize>,
+    entrance: usize,
+    exit: usize,
+}
+
+impl DungeonLayout {
+    fn neighbour(&self, index: usize, side: usize) -> Option<usize> {
+        let x = (index % self.width as usize) as i32 + SIDES[side].0;
+        let y = (index / self.width as usize) as i32 + SIDES[side].1;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.depth as i32 {
+            return None;
+        }
+        Some(x as usize + y as usize * self.width as usize)
+    }
+
+    // Two tiles are only connected if both of them are open towards each other
+    fn passage(&self, index: usize, side: usize) -> Option<usize> {
+        let neighbour = self.neighbour(index, side)?;
+        (self.open[index][side] && self.open[neighbour][opposite(side)]).then_some(neighbour)
+    }
+
+    // Sides without a wall, the entrance also opens to the outside on the -Z edge
+    fn doorway(&self, index: usize, side: usize) -> bool {
+        self.passage(index, side).is_some()
+            || (index == self.entrance && side == 3 && self.neighbour(index, side).is_none())
+    }
+
+    fn tile_center(&self, index: usize) -> Vec3 {
+        let x = (index % self.width as usize) as f32;
+        let z = (index / self.width as usize) as f32;
+        Vec3::new(x * TILE_SIZE, 0.0, z * TILE_SIZE)
+    }
+}
+
+fn dungeon_rules() -> Result<Rules<Cartesian2D>, String> {
+    let mut sockets = SocketCollection::new();
+    let wall = sockets.create();
+    let open = sockets.create();
+    sockets.add_connection(wall, vec![wall]);
+    sockets.add_connection(open, vec![open]);
+
+    let mut models = ModelCollection::<Cartesian2D>::new();
+    for module in MODULES {
+        let [x_pos, y_pos, x_neg, y_neg] = module.open_sides().map(|side| if side { open } else { wall });
+        models
+            .create(SocketsCartesian2D::Simple { x_pos, x_neg, y_pos, y_neg })
+            .with_all_rotations()
+            .with_weight(module.weight());
+    }
+
+    RulesBuilder::new_cartesian_2d(models, sockets)
+        .build()
+        .map_err(|err| format!("{:?}", err))
+}
+
+fn rotate_sides(sides: [bool; 4], rotation: ModelRotation) -> [bool; 4] {
+    let steps = match rotation {
+        ModelRotation::Rot0 => 0,
+        ModelRotation::Rot90 => 1,
+        ModelRotation::Rot180 => 2,
+        ModelRotation::Rot270 => 3,
+    };
+    let mut rotated = [false; 4];
+    for (side, open) in sides.into_iter().enumerate() {
+        rotated[(side + steps) % 4] = open;
+    }
+    rotated
+}
+
+fn generate_layout(width: u32, depth: u32, seed: u64) -> Result<DungeonLayout, String> {
+    let grid = CartesianGrid::new_cartesian_2d(width, depth, false, false);
+    let mut generator = GeneratorBuilder::new()
+        .with_rules(dungeon_rules()?)
+        .with_grid(grid)
+        .with_rng(RngMode::Seeded(seed))
+        .with_max_retry_count(20)
+        .build()
+        .map_err(|err| format!("{:?}", err))?;
+    let (_, data) = generator.generate_grid().map_err(|err| format!("{:?}", err))?;
+
+    let mut open = Vec::with_capacity((width * depth) as usize);
+    for y in 0..depth {
+        for x in 0..width {
+            let instance = data.get_2d(x, y);
+            let module = MODULES[instance.model_index];
+            open.push(rotate_sides(module.open_sides(), instance.rotation));
+        }
+    }
+
+    let tile_count = open.len();
+    let mut layout = DungeonLayout {
+        width,
+        depth,
+        open,
+        reachable: vec![false; tile_count],
+        distance: vec![usize::MAX; tile_count],
+        entrance: 0,
+        exit: 0,
+    };
+
+    // Enter through an open tile on the -Z edge, its doorway leads out of the dungeon. Then keep
+    // only what can be walked to from it.
+    layout.entrance = (0..width as usize)
+        .find(|&index| layout.open[index].iter().any(|side| *side))
+        .ok_or("no open tile on the entrance edge")?;
+
+    let mut queue = VecDeque::from([layout.entrance]);
+    layout.reachable[layout.entrance] = true;
+    layout.distance[layout.entrance] = 0;
+    while let Some(index) = queue.pop_front() {
+        for side in 0..4 {
+            let Some(neighbour) = layout.passage(index, side) else {
+                continue;
+            };
+            if !layout.reachable[neighbour] {
+                layout.reachable[neighbour] = true;
+                layout.distance[neighbour] = layout.distance[index] + 1;
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    let reachable = layout.reachable.iter().filter(|reachable| **reachable).count();
+    if reachable < MIN_DUNGEON_TILES {
+        return Err(format!("only {} connected tiles", reachable));
+    }
+    layout.exit = (0..tile_count)
+        .filter(|&index| layout.reachable[index])
+        .max_by_key(|&index| layout.distance[index])
+        .unwrap_or(layout.entrance);
+
+    Ok(layout)
+}
+
+// Generate a dungeon and spawn its floors, walls, markers and exit under a new Dungeon entity
+pub fn spawn_dungeon(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    config: &DungeonConfig,
+) -> Option<Entity> {
+    let base_seed = config.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64)
+    });
+
+    let mut generated = None;
+    for attempt in 0..MAX_GENERATION_ATTEMPTS {
+        let seed = base_seed.wrapping_add(attempt);
+        match generate_layout(config.width, config.depth, seed) {
+            Ok(layout) => {
+                generated = Some((seed, layout));
+                break;
+            }
+            Err(err) => warn!("Dungeon seed {} rejected: {}", seed, err),
+        }
+    }
+    let Some((seed, layout)) = generated else {
+        error!("Failed to generate a dungeon after {} attempts", MAX_GENERATION_ATTEMPTS);
+        return None;
+    };
+    info!("Generated dungeon with seed {}", seed);
+
+    let floor_mesh = meshes.add(Cuboid::new(TILE_SIZE, WALL_THICKNESS, TILE_SIZE));
+    let wall_mesh = meshes.add(Cuboid::new(TILE_SIZE, WALL_HEIGHT, WALL_THICKNESS));
+    let exit_mesh = meshes.add(Cylinder::new(1.5, 0.1));
+    let floor_material = materials.add(Color::srgb(0.3, 0.28, 0.26));
+    let wall_material = materials.add(Color::srgb(0.42, 0.4, 0.38));
+    let exit_material = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.2, 0.5, 1.0),
+        emissive: LinearRgba::rgb(0.5, 1.5, 4.0),
+        ..default()
+    });
+
+    let dungeon = commands
+        .spawn((
+            Dungeon {
+                seed,
+                completed: false,
+            },
+            Transform::from(&config.transform),
+            Visibility::default(),
+        ))
+        .id();
+
+    for index in 0..layout.open.len() {
+        if !layout.reachable[index] {
+            continue;
+        }
+        let center = layout.tile_center(index);
+
+        let floor = commands
+            .spawn((
+                Mesh3d(floor_mesh.clone()),
+                MeshMaterial3d(floor_material.clone()),
+                Transform::from_translation(center - Vec3::Y * WALL_THICKNESS * 0.5),
+                RigidBody::Static,
+                Collider::cuboid(TILE_SIZE, WALL_THICKNESS, TILE_SIZE),
+                GameLayer::environment(),
+            ))
+            .id();
+        commands.entity(dungeon).add_child(floor);
+
+        // Walls sit just inside the tile, so neighbours with closed sides stand back to back
+        for (side, (dx, dz)) in SIDES.into_iter().enumerate() {
+            if layout.doorway(index, side) {
+                continue;
+            }
+            let outward = Vec3::new(dx as f32, 0.0, dz as f32);
+            let offset = outward * (TILE_SIZE - WALL_THICKNESS) * 0.5 + Vec3::Y * WALL_HEIGHT * 0.5;
+            let rotation = if dx != 0 {
+                Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)
+            } else {
+                Quat::IDENTITY
+            };
+            let wall = commands
+                .spawn((
+                    Mesh3d(wall_mesh.clone()),
+                    MeshMaterial3d(wall_material.clone()),
+                    Transform::from_translation(center + offset).with_rotation(rotation),
+                    RigidBody::Static,
+                    Collider::cuboid(TILE_SIZE, WALL_HEIGHT, WALL_THICKNESS),
+                    GameLayer::environment(),
+                ))
+                .id();
+            commands.entity(dungeon).add_child(wall);
+        }
+
+        let passages = (0..4).filter(|&side| layout.passage(index, side).is_some()).count();
+        let marker = if index == layout.entrance {
+            // Where the player walks in from the outside
+            commands
+                .spawn((
+                    LevelMarker(BMarker::Destination {
+                        id: config.entrance.clone(),
+                    }),
+                    Transform::from_translation(center - Vec3::Z * (TILE_SIZE * 0.5 - 1.0)),
+                ))
+                .id()
+        } else if index == layout.exit {
+            commands
+                .spawn((
+                    Mesh3d(exit_mesh.clone()),
+                    MeshMaterial3d(exit_material.clone()),
+                    Transform::from_translation(center + Vec3::Y * 0.05),
+                    Collider::cylinder(1.5, 2.0),
+                    Sensor,
+                    GameLayer::trigger(),
+                    TriggerVolume {
+                        kind: BTrigger::DungeonExit,
+                        player_inside: false,
+                    },
+                ))
+                .id()
+        } else if passages == 1 {
+            // Dead ends are the rooms, enemies wait there
+            commands
+                .spawn((
+                    LevelMarker(BMarker::EnemySpawner {
+                        enemy: DUNGEON_ENEMY.to_string(),
+                    }),
+                    Transform::from_translation(center),
+                ))
+                .id()
+        } else {
+            continue;
+        };
+        commands.entity(dungeon).add_child(marker);
+    }
+
+    Some(dungeon)
+}
+
+fn complete_dungeons(
+    mut entered_events: EventReader<TriggerEntered>,
+    parents: Query<&Parent>,
+    mut dungeons: Query<&mut Dungeon>,
+    mut completed_events: EventWriter<DungeonCompleted>,
+    mut achievement_events: EventWriter<AchievementEvent>,
+) {
+    for event in entered_events.read() {
+        if !matches!(event.kind, BTrigger::DungeonExit) {
+            continue;
+        }
+        let Some(dungeon_entity) = parents.iter_ancestors(event.volume).find(|entity| dungeons.contains(*entity)) else {
+            continue;
+        };
+        let Ok(mut dungeon) = dungeons.get_mut(dungeon_entity) else {
+            continue;
+        };
+        if dungeon.completed {
+            continue;
+        }
+        dungeon.completed = true;
+        info!("Completed dungeon {}", dungeon.seed);
+
+        completed_events.send(DungeonCompleted {
+            dungeon: dungeon_entity,
+            seed: dungeon.seed,
+        });
+        achievement_events.send(AchievementEvent {
+            achievement_id: "dungeon_master".to_string(),
+            progress_amount: Some(1),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entrance_doorway_opens_to_the_outside_edge() {
+        let mut generated = 0;
+        for seed in 0..20 {
+            let Ok(layout) = generate_layout(6, 6, seed) else {
+                continue;
+            };
+            generated += 1;
+            assert!(layout.entrance < layout.width as usize, "seed {} entrance is not on the -Z edge", seed);
+            assert_eq!(layout.neighbour(layout.entrance, 3), None);
+            assert!(layout.doorway(layout.entrance, 3));
+        }
+        assert!(generated > 0);
+    }
+
+    #[test]
+    fn entrance_away_from_the_edge_keeps_its_wall() {
+        // Closed tile in front of an entrance on the second row
+        let mut open = vec![[false; 4]; 4];
+        open[2] = [true, false, false, true];
+        open[3] = [false, false, true, false];
+        let layout = DungeonLayout {
+            width: 2,
+            depth: 2,
+            open,
+            reachable: vec![true; 4],
+            distance: vec![0; 4],
+            entrance: 2,
+            exit: 3,
+        };
+        assert!(!layout.doorway(2, 3));
+        assert!(layout.doorway(2, 0));
+        assert!(layout.doorway(3, 2));
+    }
+}
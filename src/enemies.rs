@@ -0,0 +1,146 @@
+use avian3d::prelude::{Collider, CollisionLayers, RigidBody};
+use bevy::prelude::*;
+
+use crate::animation::PlayerAttackEvent;
+use crate::checkpoints::Respawnable;
+use crate::destructibles::{attack_damage, ATTACK_RADIUS, ATTACK_REACH};
+use crate::physics::{BMarker, GameLayer, LevelMarker};
+use crate::pickups::SoulDrop;
+use crate::player::Player;
+use crate::progression::PlayerProgress;
+
+const ENEMY_HEALTH: f32 = 60.0;
+// Souls left where an enemy dies
+const ENEMY_SOULS: u32 = 50;
+const ENEMY_RADIUS: f32 = 0.4;
+const ENEMY_HEIGHT: f32 = 1.0;
+
+pub struct EnemiesPlugin;
+
+impl Plugin for EnemiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_enemy_assets)
+            .add_systems(Update, (
+                spawn_marker_enemies,
+                damage_enemies,
+                revive_enemies,
+            ).chain());
+    }
+}
+
+// Stand-in enemy waiting at an EnemySpawner marker. It doesn't fight back yet, but it can be
+// killed and comes back when the player rests, see Respawnable.
+#[derive(Component)]
+pub struct Enemy {
+    pub kind: String,
+    pub health: f32,
+    pub max_health: f32,
+    pub souls: u32,
+}
+
+#[derive(Resource)]
+struct EnemyAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup_enemy_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(EnemyAssets {
+        mesh: meshes.add(Capsule3d::new(ENEMY_RADIUS, ENEMY_HEIGHT)),
+        material: materials.add(Color::srgb(0.35, 0.1, 0.1)),
+    });
+}
+
+// Enemies are children of their marker like pickups, so they go away with the level and
+// Respawnable keeps their spawn transform in the marker's space
+fn spawn_marker_enemies(
+    mut commands: Commands,
+    assets: Res<EnemyAssets>,
+    markers: Query<(Entity, &LevelMarker), Added<LevelMarker>>,
+) {
+    for (entity, marker) in &markers {
+        let BMarker::EnemySpawner { enemy } = &marker.0 else {
+            continue;
+        };
+        // The capsule is centered, lift it so it stands on the marker
+        let spawn = Transform::from_translation(Vec3::Y * (ENEMY_HEIGHT / 2.0 + ENEMY_RADIUS));
+        let enemy = commands.spawn((
+            Name::new(format!("Enemy {}", enemy)),
+            Mesh3d(assets.mesh.clone()),
+            MeshMaterial3d(assets.material.clone()),
+            spawn,
+            RigidBody::Kinematic,
+            Collider::capsule(ENEMY_RADIUS, ENEMY_HEIGHT),
+            GameLayer::enemy(),
+            Enemy {
+                kind: enemy.clone(),
+                health: ENEMY_HEALTH,
+                max_health: ENEMY_HEALTH,
+                souls: ENEMY_SOULS,
+            },
+            Respawnable {
+                spawn,
+                defeated: false,
+            },
+        )).id();
+        commands.entity(entity).add_child(enemy);
+    }
+}
+
+// Swings hit enemies the same way they hit destructibles. Defeated enemies are hidden and stop
+// colliding instead of being despawned, so resting can bring them back.
+fn damage_enemies(
+    mut attack_events: EventReader<PlayerAttackEvent>,
+    player_query: Query<&Transform, With<Player>>,
+    player_progress: Res<PlayerProgress>,
+    mut enemies: Query<(&mut Enemy, &mut Respawnable, &GlobalTransform, &mut Visibility, &mut CollisionLayers)>,
+    mut drop_events: EventWriter<SoulDrop>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    for attack in attack_events.read() {
+        // The character model faces +Z, see apply_controls
+        let facing = *player_transform.back();
+        let hit_center = player_transform.translation + Vec3::Y + facing * ATTACK_REACH;
+        let damage = attack_damage(player_progress.strength, attack.combo_stage);
+
+        for (mut enemy, mut respawnable, transform, mut visibility, mut layers) in &mut enemies {
+            if respawnable.defeated || transform.translation().distance(hit_center) > ATTACK_RADIUS + ENEMY_RADIUS {
+                continue;
+            }
+            enemy.health -= damage;
+            info!("Hit {} enemy for {:.0} damage ({:.0}/{:.0})", enemy.kind, damage, enemy.health.max(0.0), enemy.max_health);
+            if enemy.health > 0.0 {
+                continue;
+            }
+            respawnable.defeated = true;
+            *visibility = Visibility::Hidden;
+            *layers = CollisionLayers::NONE;
+            info!("Defeated {} enemy", enemy.kind);
+            // Dropped at the enemy's feet, the pickup adds its own hover height
+            drop_events.send(SoulDrop {
+                position: transform.translation() - Vec3::Y * (ENEMY_HEIGHT / 2.0 + ENEMY_RADIUS),
+                souls: enemy.souls,
+            });
+        }
+    }
+}
+
+// reset_respawnables puts the enemy back in place, this gives it back its health and collisions
+fn revive_enemies(
+    mut enemies: Query<(&mut Enemy, &Respawnable, &mut CollisionLayers), Changed<Respawnable>>,
+) {
+    for (mut enemy, respawnable, mut layers) in &mut enemies {
+        if respawnable.defeated || enemy.health > 0.0 {
+            continue;
+        }
+        enemy.health = enemy.max_health;
+        *layers = GameLayer::enemy();
+    }
+}
@@ -0,0 +1,515 @@
+use bevy::{asset::io::file::FileAssetReader, input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+// Player bindings, written with the defaults the first time the game starts
+const CONFIG_DIR: &str = "config";
+const BINDINGS_FILE: &str = "input.json";
+
+// Next to the assets folder like world::levels_dir, so it doesn't depend on the working directory
+fn bindings_path() -> PathBuf {
+    FileAssetReader::get_base_path().join(CONFIG_DIR).join(BINDINGS_FILE)
+}
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load_or_default())
+            .init_resource::<ActionState>()
+            .init_resource::<StickSettings>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem))
+            .add_systems(Update, save_changed_bindings);
+    }
+}
+
+// Everything the game reacts to, systems read these instead of raw keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Jump,
+    Roll,
+    Attack,
+    Interact,
+    Quit,
+    // Debug and tuning keys
+    DebugCamera,
+    DebugPauseTime,
+    DebugLongerDays,
+    DebugShorterDays,
+    DebugCycleWeather,
+    DebugNavmesh,
+    DebugGainSouls,
+    DebugDamage,
+    DebugHeal,
+    DebugShieldHit,
+    DebugShieldRestore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    // Button on any connected gamepad
+    Gamepad(GamepadButton),
+}
+
+impl InputBinding {
+    fn is_gamepad(&self) -> bool {
+        matches!(self, InputBinding::Gamepad(_))
+    }
+}
+
+// How raw stick positions turn into movement and camera input
+#[derive(Resource, Debug, Clone)]
+pub struct StickSettings {
+    // Stick positions closer to the center than this are ignored, drifting sticks rest a bit off it
+    pub inner_deadzone: f32,
+    // Anything further out than this counts as full tilt
+    pub outer_deadzone: f32,
+    // Exponent applied to the camera stick, above 1 gives finer control near the center
+    pub look_curve: f32,
+    // Camera turn rate at full tilt in radians per second
+    pub look_speed: f32,
+    // Extra turn rate reached after holding the camera stick at full tilt for look_ramp_time
+    pub look_boost: f32,
+    pub look_ramp_time: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self {
+            inner_deadzone: 0.15,
+            outer_deadzone: 0.95,
+            look_curve: 2.0,
+            look_speed: 3.0,
+            look_boost: 1.5,
+            look_ramp_time: 0.6,
+        }
+    }
+}
+
+impl StickSettings {
+    // Radial deadzone, rescaled so movement starts from zero right past the inner edge
+    pub fn apply_deadzone(&self, stick: Vec2) -> Vec2 {
+        let length = stick.length();
+        if length <= self.inner_deadzone {
+            return Vec2::ZERO;
+        }
+        let scaled = ((length - self.inner_deadzone) / (self.outer_deadzone - self.inner_deadzone)).min(1.0);
+        stick / length * scaled
+    }
+}
+
+// Which inputs trigger each action, an action can have several
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings(pub BTreeMap<Action, Vec<InputBinding>>);
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use InputBinding::{Gamepad, Key, Mouse};
+        Self(BTreeMap::from([
+            (Action::MoveForward, vec![Key(KeyCode::KeyW)]),
+            (Action::MoveBack, vec![Key(KeyCode::KeyS)]),
+            (Action::MoveLeft, vec![Key(KeyCode::KeyA)]),
+            (Action::MoveRight, vec![Key(KeyCode::KeyD)]),
+            (Action::Sprint, vec![Key(KeyCode::ShiftLeft), Gamepad(GamepadButton::LeftThumb)]),
+            (Action::Jump, vec![Key(KeyCode::ControlLeft), Gamepad(GamepadButton::South)]),
+            (Action::Roll, vec![Key(KeyCode::Space), Gamepad(GamepadButton::East)]),
+            (Action::Attack, vec![Mouse(MouseButton::Left), Gamepad(GamepadButton::RightTrigger2)]),
+            (Action::Interact, vec![Key(KeyCode::KeyE), Gamepad(GamepadButton::West)]),
+            (Action::Quit, vec![Key(KeyCode::Escape)]),
+            (Action::DebugCamera, vec![Key(KeyCode::F1)]),
+            (Action::DebugPauseTime, vec![Key(KeyCode::F2)]),
+            (Action::DebugLongerDays, vec![Key(KeyCode::F3)]),
+            (Action::DebugShorterDays, vec![Key(KeyCode::F4)]),
+            (Action::DebugCycleWeather, vec![Key(KeyCode::F5)]),
+            (Action::DebugNavmesh, vec![Key(KeyCode::F6)]),
+            (Action::DebugGainSouls, vec![Key(KeyCode::KeyG)]),
+            (Action::DebugDamage, vec![Key(KeyCode::KeyH)]),
+            (Action::DebugHeal, vec![Key(KeyCode::KeyJ)]),
+            (Action::DebugShieldHit, vec![Key(KeyCode::KeyK)]),
+            (Action::DebugShieldRestore, vec![Key(KeyCode::KeyL)]),
+        ]))
+    }
+}
+
+impl InputBindings {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+        Self::from_json(&text).map_err(|err| format!("{} {}", path.display(), err))
+    }
+
+    fn from_json(text: &str) -> Result<Self, String> {
+        let deserializer = &mut serde_json::Deserializer::from_str(text);
+        let mut bindings: Self = serde_path_to_error::deserialize(deserializer)
+            .map_err(|err| format!("at {}: {}", err.path(), err.inner()))?;
+        // Attack used to default to RightTrigger, which is the right bumper. Files from then get
+        // the trigger instead, unless the player put it on something else.
+        let trigger = InputBinding::Gamepad(GamepadButton::RightTrigger2);
+        if bindings.actions_for(trigger).next().is_none() {
+            if let Some(attack) = bindings.0.get_mut(&Action::Attack) {
+                for binding in attack.iter_mut() {
+                    if *binding == InputBinding::Gamepad(GamepadButton::RightTrigger) {
+                        *binding = trigger;
+                    }
+                }
+            }
+        }
+        for (action, defaults) in Self::default().0 {
+            match bindings.0.get(&action).map(|loaded| loaded.iter().any(InputBinding::is_gamepad)) {
+                // Actions added since the file was written get their default bindings, unless
+                // the player already uses those inputs for something else
+                None => {
+                    if let Err(taken) = bindings.rebind(action, defaults) {
+                        warn!("{:?} is left unbound, its default inputs are used by {:?}", action, taken);
+                        bindings.0.insert(action, Vec::new());
+                    }
+                }
+                // Files written before gamepad support only have keyboard and mouse bindings,
+                // those actions get the default buttons that are still free
+                Some(false) => {
+                    let buttons: Vec<InputBinding> = defaults
+                        .into_iter()
+                        .filter(|binding| binding.is_gamepad() && bindings.actions_for(*binding).next().is_none())
+                        .collect();
+                    bindings.0.entry(action).or_default().extend(buttons);
+                }
+                Some(true) => {}
+            }
+        }
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|err| format!("can't create {}: {}", directory.display(), err))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, text).map_err(|err| format!("can't write {}: {}", path.display(), err))
+    }
+
+    fn load_or_default() -> Self {
+        let path = bindings_path();
+        let bindings = if path.exists() {
+            Self::load(&path).unwrap_or_else(|err| {
+                error!("Failed to load input bindings {}, using the defaults", err);
+                Self::default()
+            })
+        } else {
+            let bindings = Self::default();
+            if let Err(err) = bindings.save(&path) {
+                warn!("Failed to write default input bindings {}", err);
+            }
+            bindings
+        };
+        for (binding, actions) in bindings.conflicts() {
+            warn!("{:?} is bound to several actions: {:?}", binding, actions);
+        }
+        bindings
+    }
+
+    pub fn get(&self, action: Action) -> &[InputBinding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // Inputs bound to more than one action
+    pub fn conflicts(&self) -> Vec<(InputBinding, Vec<Action>)> {
+        let mut conflicts: Vec<(InputBinding, Vec<Action>)> = Vec::new();
+        for (action, bindings) in &self.0 {
+            for binding in bindings {
+                let actions: Vec<Action> = self.actions_for(*binding).collect();
+                if actions.len() > 1 && actions[0] == *action {
+                    conflicts.push((*binding, actions));
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn actions_for(&self, binding: InputBinding) -> impl Iterator<Item = Action> + '_ {
+        self.0
+            .iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action)
+    }
+
+    // Replace the bindings of an action. Refused with the actions already using one of the
+    // inputs, unbind those first.
+    pub fn rebind(&mut self, action: Action, bindings: Vec<InputBinding>) -> Result<(), Vec<Action>> {
+        let mut taken: Vec<Action> = bindings
+            .iter()
+            .flat_map(|binding| self.actions_for(*binding))
+            .filter(|other| *other != action)
+            .collect();
+        if !taken.is_empty() {
+            taken.sort();
+            taken.dedup();
+            return Err(taken);
+        }
+        self.0.insert(action, bindings);
+        Ok(())
+    }
+}
+
+// Actions held and pressed this frame, built from the raw input in PreUpdate
+#[derive(Resource, Default)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    // x is right, y is forward, at most 1 long
+    move_axis: Vec2,
+    // x is right, y is up, after the deadzone and curve
+    look_axis: Vec2,
+}
+
+impl ActionState {
+    // Movement keys and the left stick combined, analog sticks give lengths below 1
+    pub fn move_axis(&self) -> Vec2 {
+        self.move_axis
+    }
+
+    // Right stick, the camera scales it by StickSettings::look_speed
+    pub fn look_axis(&self) -> Vec2 {
+        self.look_axis
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+// Keys count as full tilt in their direction, holding them while pushing the stick can't go
+// faster than either alone
+fn combine_move_axis(keys: Vec2, stick: Vec2) -> Vec2 {
+    (keys.normalize_or_zero() + stick).clamp_length_max(1.0)
+}
+
+fn update_action_state(
+    bindings: Res<InputBindings>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    sticks: Res<StickSettings>,
+    mut state: ResMut<ActionState>,
+) {
+    let state = &mut *state;
+    let previously_pressed = std::mem::take(&mut state.pressed);
+    state.just_pressed.clear();
+
+    // With several gamepads connected the stick pushed furthest wins
+    let strongest = |stick: fn(&Gamepad) -> Vec2| {
+        gamepads
+            .iter()
+            .map(|gamepad| sticks.apply_deadzone(stick(gamepad)))
+            .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+            .unwrap_or(Vec2::ZERO)
+    };
+    let left_stick = strongest(Gamepad::left_stick);
+    let right_stick = strongest(Gamepad::right_stick);
+
+    for (action, action_bindings) in &bindings.0 {
+        let (pressed, just_pressed) = action_bindings.iter().fold((false, false), |(pressed, just_pressed), binding| {
+            match binding {
+                InputBinding::Key(key) => (pressed || keyboard.pressed(*key), just_pressed || keyboard.just_pressed(*key)),
+                InputBinding::Mouse(button) => (pressed || mouse.pressed(*button), just_pressed || mouse.just_pressed(*button)),
+                InputBinding::Gamepad(button) => (
+                    pressed || gamepads.iter().any(|gamepad| gamepad.pressed(*button)),
+                    just_pressed || gamepads.iter().any(|gamepad| gamepad.just_pressed(*button)),
+                ),
+            }
+        });
+        if pressed {
+            state.pressed.insert(*action);
+        }
+        // A second binding going down while the first is held doesn't press the action again
+        if just_pressed && !previously_pressed.contains(action) {
+            state.just_pressed.insert(*action);
+        }
+    }
+
+    let axis = |positive: Action, negative: Action| {
+        state.pressed.contains(&positive) as i32 as f32 - state.pressed.contains(&negative) as i32 as f32
+    };
+    let keys = Vec2::new(axis(Action::MoveRight, Action::MoveLeft), axis(Action::MoveForward, Action::MoveBack));
+    state.move_axis = combine_move_axis(keys, left_stick);
+    state.look_axis = right_stick.normalize_or_zero() * right_stick.length().powf(sticks.look_curve);
+}
+
+fn save_changed_bindings(bindings: Res<InputBindings>) {
+    // Loading counts as a change, but the file is already up to date then
+    if !bindings.is_changed() || bindings.is_added() {
+        return;
+    }
+    let path = bindings_path();
+    match bindings.save(&path) {
+        Ok(()) => info!("Saved input bindings to {}", path.display()),
+        Err(err) => error!("Failed to save input bindings {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        assert!(InputBindings::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn conflicts_list_every_action_sharing_an_input() {
+        let mut bindings = InputBindings::default();
+        bindings.0.insert(Action::Sprint, vec![InputBinding::Key(KeyCode::Space)]);
+        bindings.0.insert(Action::Jump, vec![InputBinding::Key(KeyCode::Space)]);
+        // Reported once, not once per action
+        assert_eq!(
+            bindings.conflicts(),
+            vec![(InputBinding::Key(KeyCode::Space), vec![Action::Sprint, Action::Jump, Action::Roll])]
+        );
+    }
+
+    #[test]
+    fn rebind_refuses_inputs_of_other_actions() {
+        let mut bindings = InputBindings::default();
+        let taken = bindings.rebind(Action::Jump, vec![InputBinding::Key(KeyCode::Space), InputBinding::Key(KeyCode::KeyE)]);
+        assert_eq!(taken, Err(vec![Action::Roll, Action::Interact]));
+        assert_eq!(bindings, InputBindings::default());
+
+        // Keeping one of its own inputs is fine
+        let jump = vec![InputBinding::Key(KeyCode::ControlLeft), InputBinding::Key(KeyCode::KeyV)];
+        assert_eq!(bindings.rebind(Action::Jump, jump.clone()), Ok(()));
+        assert_eq!(bindings.get(Action::Jump), jump.as_slice());
+    }
+
+    #[test]
+    fn load_adds_actions_missing_from_the_file() {
+        let bindings = InputBindings::from_json(r#"{"Jump": [{"Key": "KeyV"}, {"Gamepad": "South"}]}"#).unwrap();
+        assert_eq!(
+            bindings.get(Action::Jump),
+            &[InputBinding::Key(KeyCode::KeyV), InputBinding::Gamepad(GamepadButton::South)]
+        );
+        assert_eq!(bindings.get(Action::Roll), InputBindings::default().get(Action::Roll));
+        assert_eq!(bindings.0.len(), InputBindings::default().0.len());
+    }
+
+    #[test]
+    fn load_leaves_new_actions_unbound_when_their_inputs_are_taken() {
+        let bindings = InputBindings::from_json(r#"{"Jump": [{"Key": "KeyE"}, {"Gamepad": "South"}]}"#).unwrap();
+        assert_eq!(
+            bindings.get(Action::Jump),
+            &[InputBinding::Key(KeyCode::KeyE), InputBinding::Gamepad(GamepadButton::South)]
+        );
+        assert!(bindings.get(Action::Interact).is_empty());
+    }
+
+    #[test]
+    fn load_adds_default_buttons_to_keyboard_only_actions() {
+        let bindings = InputBindings::from_json(r#"{"Jump": [{"Key": "KeyV"}]}"#).unwrap();
+        assert_eq!(
+            bindings.get(Action::Jump),
+            &[InputBinding::Key(KeyCode::KeyV), InputBinding::Gamepad(GamepadButton::South)]
+        );
+
+        // Actions that already have a button keep just that one
+        let bindings = InputBindings::from_json(r#"{"Jump": [{"Gamepad": "North"}]}"#).unwrap();
+        assert_eq!(bindings.get(Action::Jump), &[InputBinding::Gamepad(GamepadButton::North)]);
+
+        // Buttons the player moved to another action aren't added back
+        let bindings = InputBindings::from_json(
+            r#"{"Jump": [{"Key": "ControlLeft"}], "Roll": [{"Key": "Space"}, {"Gamepad": "South"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(bindings.get(Action::Jump), &[InputBinding::Key(KeyCode::ControlLeft)]);
+        assert!(bindings.conflicts().is_empty());
+    }
+
+    #[test]
+    fn load_moves_attack_from_the_bumper_to_the_trigger() {
+        let bindings = InputBindings::from_json(r#"{"Attack": [{"Mouse": "Left"}, {"Gamepad": "RightTrigger"}]}"#).unwrap();
+        assert_eq!(
+            bindings.get(Action::Attack),
+            &[InputBinding::Mouse(MouseButton::Left), InputBinding::Gamepad(GamepadButton::RightTrigger2)]
+        );
+
+        // Files from before gamepad support get the trigger with the other default buttons
+        let bindings = InputBindings::from_json(r#"{"Attack": [{"Mouse": "Left"}]}"#).unwrap();
+        assert_eq!(
+            bindings.get(Action::Attack),
+            &[InputBinding::Mouse(MouseButton::Left), InputBinding::Gamepad(GamepadButton::RightTrigger2)]
+        );
+
+        // The bumper stays when the trigger is already used for something else
+        let bindings = InputBindings::from_json(
+            r#"{"Attack": [{"Gamepad": "RightTrigger"}], "Roll": [{"Gamepad": "RightTrigger2"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(bindings.get(Action::Attack), &[InputBinding::Gamepad(GamepadButton::RightTrigger)]);
+        assert!(bindings.conflicts().is_empty());
+    }
+
+    #[test]
+    fn load_reports_where_the_file_is_wrong() {
+        let err = InputBindings::from_json(r#"{"Jump": [{"Key": "Kee"}]}"#).unwrap_err();
+        assert!(err.starts_with("at Jump[0]"), "{}", err);
+    }
+
+    fn sticks() -> StickSettings {
+        StickSettings {
+            inner_deadzone: 0.2,
+            outer_deadzone: 0.8,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn deadzone_ignores_the_center() {
+        assert_eq!(sticks().apply_deadzone(Vec2::ZERO), Vec2::ZERO);
+        assert_eq!(sticks().apply_deadzone(Vec2::new(0.1, -0.1)), Vec2::ZERO);
+        assert_eq!(sticks().apply_deadzone(Vec2::new(0.0, 0.19)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn deadzone_rescales_between_the_edges() {
+        // Starts from zero right past the inner edge
+        assert!(sticks().apply_deadzone(Vec2::new(0.21, 0.0)).length() < 0.02);
+        // Halfway between the edges is half tilt, in the same direction
+        let half = sticks().apply_deadzone(Vec2::new(0.0, -0.5));
+        assert!(half.abs_diff_eq(Vec2::new(0.0, -0.5), 1e-5), "{}", half);
+        let diagonal = sticks().apply_deadzone(Vec2::new(0.5, 0.5));
+        assert!((diagonal.x - diagonal.y).abs() < 1e-5, "{}", diagonal);
+    }
+
+    #[test]
+    fn deadzone_reaches_full_tilt_at_the_outer_edge() {
+        assert!((sticks().apply_deadzone(Vec2::new(0.8, 0.0)).length() - 1.0).abs() < 1e-5);
+        let beyond = sticks().apply_deadzone(Vec2::new(0.0, 1.0));
+        assert!(beyond.abs_diff_eq(Vec2::Y, 1e-5), "{}", beyond);
+    }
+
+    #[test]
+    fn move_axis_combines_keys_and_stick() {
+        // Diagonal keys aren't faster than straight ones
+        let diagonal = combine_move_axis(Vec2::new(1.0, 1.0), Vec2::ZERO);
+        assert!((diagonal.length() - 1.0).abs() < 1e-5);
+        // The stick alone keeps its tilt
+        assert_eq!(combine_move_axis(Vec2::ZERO, Vec2::new(0.0, 0.5)), Vec2::new(0.0, 0.5));
+        // Both in the same direction don't add up past full speed
+        assert!(combine_move_axis(Vec2::Y, Vec2::new(0.0, 0.7)).abs_diff_eq(Vec2::Y, 1e-5));
+        // Opposite directions cancel out
+        assert!(combine_move_axis(Vec2::Y, Vec2::NEG_Y).abs_diff_eq(Vec2::ZERO, 1e-5));
+        // Different directions point between them
+        let mixed = combine_move_axis(Vec2::X, Vec2::new(0.0, 0.5));
+        assert!((mixed.length() - 1.0).abs() < 1e-5);
+        assert!(mixed.x > mixed.y && mixed.y > 0.0, "{}", mixed);
+    }
+}
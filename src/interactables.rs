@@ -0,0 +1,419 @@
+use avian3d::prelude::{AngularVelocity, LinearVelocity, Position, RigidBody, Rotation};
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::input::{Action, ActionState};
+use crate::npcs::spawn_feedback_text;
+use crate::physics::BInteractable;
+use crate::player::Player;
+use crate::transitions::LevelTransition;
+use crate::ui::GameUI;
+use crate::world::LevelSelection;
+
+// How close the player has to be to use doors, levers and chests
+const DEFAULT_INTERACTION_RANGE: f32 = 2.5;
+// Seconds a door takes to swing fully open or closed
+const DOOR_SWING_TIME: f32 = 0.8;
+// How far levers tilt either way, in degrees
+const LEVER_ANGLE: f32 = 35.0;
+
+pub struct InteractablesPlugin;
+
+impl Plugin for InteractablesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InteractionFocus>()
+            .init_resource::<InteractableStates>()
+            .add_event::<InteractEvent>()
+            .add_event::<InteractableToggled>()
+            .add_systems(Startup, spawn_interaction_prompt)
+            .add_systems(Update, (
+                restore_interactable_states,
+                update_interaction_focus,
+                send_interactions,
+                use_interactables,
+                animate_levers,
+            ).chain())
+            // Doors are kinematic bodies steered through their velocity, in step with physics
+            .add_systems(FixedUpdate, animate_doors);
+    }
+}
+
+// Anything the player can use with the interact key while standing close enough
+#[derive(Component)]
+pub struct Interactable {
+    pub prompt: String,
+    pub range: f32,
+    // Looted chests stay in the level but can't be used again
+    pub enabled: bool,
+}
+
+impl Interactable {
+    pub fn new(prompt: impl Into<String>, range: f32) -> Self {
+        Self {
+            prompt: prompt.into(),
+            range,
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Door {
+    pub id: String,
+    pub open: bool,
+    pub locked: bool,
+    pub angle: f32,
+    pub hinge: Vec3,
+    // 0 closed, 1 fully open
+    openness: f32,
+    // Transform the level placed the door at, captured once it spawned
+    closed: Option<Transform>,
+}
+
+impl Door {
+    // Closed transform rotated around the hinge by how far the door is open
+    fn swung_transform(&self, closed: Transform) -> Transform {
+        // Smoothstep so the door eases in and out
+        let t = self.openness * self.openness * (3.0 - 2.0 * self.openness);
+        let swing = Quat::from_rotation_y((self.angle * t).to_radians());
+        let hinge = closed.scale * self.hinge;
+        Transform {
+            translation: closed.translation + closed.rotation * (hinge - swing * hinge),
+            rotation: closed.rotation * swing,
+            scale: closed.scale,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Lever {
+    pub id: String,
+    pub targets: Vec<String>,
+    pub on: bool,
+    rest: Option<Quat>,
+}
+
+#[derive(Component)]
+pub struct Chest {
+    pub id: String,
+    pub souls: u32,
+    pub opened: bool,
+}
+
+// Interactable the player is closest to, if any is in range
+#[derive(Resource, Default)]
+pub struct InteractionFocus(pub Option<Entity>);
+
+#[derive(Component)]
+pub struct InteractionPrompt;
+
+// The player pressed the interact key while focusing this entity
+#[derive(Event)]
+pub struct InteractEvent {
+    pub entity: Entity,
+}
+
+// A door opened or closed, a lever was pulled or a chest looted
+#[derive(Event)]
+pub struct InteractableToggled {
+    pub id: String,
+    pub active: bool,
+}
+
+// Open doors, pulled levers and looted chests by level and id. Restored whenever a level
+// spawns them again and serializable so it can go into a save file.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct InteractableStates {
+    pub levels: HashMap<String, HashMap<String, bool>>,
+}
+
+impl InteractableStates {
+    pub fn get(&self, level: &str, id: &str) -> Option<bool> {
+        self.levels.get(level).and_then(|states| states.get(id)).copied()
+    }
+
+    pub fn set(&mut self, level: &str, id: &str, active: bool) {
+        self.levels.entry(level.to_string()).or_default().insert(id.to_string(), active);
+    }
+}
+
+fn door_prompt(door: &Door) -> &'static str {
+    match (door.open, door.locked) {
+        (true, _) => "Press E to close",
+        (false, true) => "Locked",
+        (false, false) => "Press E to open",
+    }
+}
+
+// Add the components for a door, lever or chest authored in a level
+pub fn insert_interactable(entity: &mut EntityCommands, interactable: &BInteractable) {
+    match interactable.clone() {
+        BInteractable::Door { id, open, locked, angle, hinge } => {
+            let door = Door {
+                id,
+                open,
+                locked,
+                angle,
+                hinge,
+                openness: 0.0,
+                closed: None,
+            };
+            entity.insert((Interactable::new(door_prompt(&door), DEFAULT_INTERACTION_RANGE), door));
+        }
+        BInteractable::Lever { id, targets, on } => {
+            entity.insert((
+                Interactable::new("Press E to pull", DEFAULT_INTERACTION_RANGE),
+                Lever { id, targets, on, rest: None },
+            ));
+        }
+        BInteractable::Chest { id, souls } => {
+            entity.insert((
+                Interactable::new("Press E to open chest", DEFAULT_INTERACTION_RANGE),
+                Chest { id, souls, opened: false },
+            ));
+        }
+    }
+}
+
+fn spawn_interaction_prompt(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(100.0),
+            left: Val::Percent(50.0),
+            display: Display::None, // Hidden until something is in range
+            ..default()
+        },
+        InteractionPrompt,
+    ));
+}
+
+// Put freshly spawned interactables back into the state they were left in
+fn restore_interactable_states(
+    mut commands: Commands,
+    states: Res<InteractableStates>,
+    level: Res<LevelSelection>,
+    mut doors: Query<(Entity, &mut Door, &mut Interactable, &mut Transform), Added<Door>>,
+    mut levers: Query<(&mut Lever, &Transform), Added<Lever>>,
+    mut chests: Query<(&mut Chest, &mut Interactable), (Added<Chest>, Without<Door>)>,
+) {
+    for (entity, mut door, mut interactable, mut transform) in &mut doors {
+        if let Some(open) = states.get(&level.name, &door.id) {
+            door.open = open;
+        }
+        // Doors are moved with velocities so they push the player instead of teleporting
+        // through them
+        commands.entity(entity).insert((RigidBody::Kinematic, LinearVelocity::ZERO, AngularVelocity::ZERO));
+        door.openness = if door.open { 1.0 } else { 0.0 };
+        door.closed = Some(*transform);
+        *transform = door.swung_transform(*transform);
+        interactable.prompt = door_prompt(&door).to_string();
+    }
+    for (mut lever, transform) in &mut levers {
+        if let Some(on) = states.get(&level.name, &lever.id) {
+            lever.on = on;
+        }
+        lever.rest = Some(transform.rotation);
+    }
+    for (mut chest, mut interactable) in &mut chests {
+        if states.get(&level.name, &chest.id) == Some(true) {
+            chest.opened = true;
+            interactable.enabled = false;
+        }
+    }
+}
+
+// Focus the closest interactable in range and show its prompt
+fn update_interaction_focus(
+    interactables: Query<(Entity, &Interactable, &GlobalTransform)>,
+    player_query: Query<&Transform, With<Player>>,
+    mut focus: ResMut<InteractionFocus>,
+    mut prompt_query: Query<(&mut Text, &mut Node), With<InteractionPrompt>>,
+) {
+    let closest = player_query.get_single().ok().and_then(|player| {
+        interactables
+            .iter()
+            .filter(|(_, interactable, _)| interactable.enabled)
+            .map(|(entity, interactable, transform)| {
+                (entity, interactable, transform.translation().distance(player.translation))
+            })
+            .filter(|(_, interactable, distance)| *distance <= interactable.range)
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+    });
+    focus.0 = closest.map(|(entity, _, _)| entity);
+
+    let Ok((mut text, mut node)) = prompt_query.get_single_mut() else {
+        return;
+    };
+    match closest {
+        Some((_, interactable, _)) => {
+            if text.0 != interactable.prompt {
+                text.0 = interactable.prompt.clone();
+            }
+            node.display = Display::Flex;
+        }
+        None => node.display = Display::None,
+    }
+}
+
+fn send_interactions(
+    actions: Res<ActionState>,
+    focus: Res<InteractionFocus>,
+    transition: Res<LevelTransition>,
+    player_query: Query<&Player>,
+    mut interact_events: EventWriter<InteractEvent>,
+) {
+    if transition.is_active() || !actions.just_pressed(Action::Interact) {
+        return;
+    }
+    if player_query.get_single().is_ok_and(|player| player.is_dead) {
+        return;
+    }
+    if let Some(entity) = focus.0 {
+        interact_events.send(InteractEvent { entity });
+    }
+}
+
+fn use_interactables(
+    mut commands: Commands,
+    mut interact_events: EventReader<InteractEvent>,
+    mut toggled_events: EventWriter<InteractableToggled>,
+    mut states: ResMut<InteractableStates>,
+    level: Res<LevelSelection>,
+    mut game_ui: ResMut<GameUI>,
+    mut doors: Query<(&mut Door, &mut Interactable)>,
+    mut levers: Query<(Entity, &mut Lever)>,
+    mut chests: Query<(&mut Chest, &mut Interactable), Without<Door>>,
+) {
+    let mut toggle = |id: &str, active: bool| {
+        states.set(&level.name, id, active);
+        toggled_events.send(InteractableToggled { id: id.to_string(), active });
+    };
+
+    for event in interact_events.read() {
+        if let Ok((mut door, mut interactable)) = doors.get_mut(event.entity) {
+            if door.locked && !door.open {
+                info!("Door {} is locked", door.id);
+                continue;
+            }
+            door.open = !door.open;
+            interactable.prompt = door_prompt(&door).to_string();
+            toggle(&door.id, door.open);
+        }
+
+        if let Ok((mut chest, mut interactable)) = chests.get_mut(event.entity) {
+            if chest.opened {
+                continue;
+            }
+            chest.opened = true;
+            interactable.enabled = false;
+            game_ui.souls += chest.souls as usize;
+            toggle(&chest.id, true);
+            info!("Opened chest {}, found {} souls", chest.id, chest.souls);
+            spawn_feedback_text(&mut commands, format!("+ {} souls", chest.souls), 200.0, 2.0);
+        }
+
+        let Ok((_, mut lever)) = levers.get_mut(event.entity) else {
+            continue;
+        };
+        lever.on = !lever.on;
+        toggle(&lever.id, lever.on);
+        let targets = lever.targets.clone();
+        info!("Lever {} pulled, toggling {:?}", lever.id, targets);
+
+        // Levers ignore locks, that's what they're for
+        for (mut door, mut interactable) in &mut doors {
+            if targets.contains(&door.id) {
+                door.open = !door.open;
+                interactable.prompt = door_prompt(&door).to_string();
+                toggle(&door.id, door.open);
+            }
+        }
+        for (entity, mut target) in &mut levers {
+            // Levers can be chained, but the pulled one doesn't flip itself back
+            if entity == event.entity || !targets.contains(&target.id) {
+                continue;
+            }
+            target.on = !target.on;
+            toggle(&target.id, target.on);
+        }
+    }
+}
+
+// Swing doors towards their open or closed rotation around the hinge. The kinematic body gets
+// the velocities that take it to this step's swing, since the target is recomputed every step
+// any drift from the physics step doesn't add up.
+fn animate_doors(
+    time: Res<Time<Fixed>>,
+    mut doors: Query<(
+        Entity,
+        &mut Door,
+        &mut Transform,
+        &Position,
+        &Rotation,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+    )>,
+    parents: Query<&Parent>,
+    global_transforms: Query<&GlobalTransform>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut door, mut transform, position, rotation, mut linear_velocity, mut angular_velocity) in &mut doors {
+        let Some(closed) = door.closed else {
+            continue;
+        };
+        let target = if door.open { 1.0 } else { 0.0 };
+        if door.openness == target {
+            if linear_velocity.0 != Vec3::ZERO || angular_velocity.0 != Vec3::ZERO {
+                // Done swinging, stop and settle exactly on the end pose
+                linear_velocity.0 = Vec3::ZERO;
+                angular_velocity.0 = Vec3::ZERO;
+                *transform = door.swung_transform(closed);
+            }
+            continue;
+        }
+        if dt <= 0.0 {
+            continue;
+        }
+        let step = dt / DOOR_SWING_TIME;
+        door.openness = if door.openness < target {
+            (door.openness + step).min(target)
+        } else {
+            (door.openness - step).max(target)
+        };
+
+        // Door meshes of glb levels sit under their node, velocities are in world space. The
+        // door's own pose comes from physics, its GlobalTransform lags behind the fixed steps.
+        let parent_transform = parents
+            .get(entity)
+            .ok()
+            .and_then(|parent| global_transforms.get(parent.get()).ok())
+            .copied()
+            .unwrap_or_default();
+        let goal = parent_transform.mul_transform(door.swung_transform(closed));
+        linear_velocity.0 = (goal.translation() - position.0) / dt;
+        let (axis, angle) = (goal.rotation() * rotation.0.inverse()).to_axis_angle();
+        // to_axis_angle gives 0..2π, take the short way around
+        let angle = if angle > std::f32::consts::PI { angle - std::f32::consts::TAU } else { angle };
+        angular_velocity.0 = axis * angle / dt;
+    }
+}
+
+fn animate_levers(
+    time: Res<Time>,
+    mut levers: Query<(&Lever, &mut Transform)>,
+) {
+    for (lever, mut transform) in &mut levers {
+        let Some(rest) = lever.rest else {
+            continue;
+        };
+        let angle = if lever.on { LEVER_ANGLE } else { -LEVER_ANGLE };
+        let target = rest * Quat::from_rotation_x(angle.to_radians());
+        if transform.rotation.angle_between(target) < 0.001 {
+            continue;
+        }
+        transform.rotation = transform.rotation.slerp(target, (time.delta_secs() * 10.0).min(1.0));
+    }
+}
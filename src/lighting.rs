@@ -2,7 +2,73 @@ use bevy::{
     prelude::*,
     pbr::CascadeShadowConfigBuilder
 };
+use std::f32::consts::PI;
 
+use crate::input::{Action, ActionState};
+use crate::shader::AuroraShaderMaterial;
+use crate::world::LevelManifest;
+
+// Clear color at noon, nights use the level's sky_color
+pub const DAY_SKY_COLOR: Color = Color::srgb(0.45, 0.6, 0.85);
+// Fog color at noon and at midnight
+const DAY_FOG_COLOR: Color = Color::srgb(0.6, 0.65, 0.75);
+const NIGHT_FOG_COLOR: Color = Color::srgb(43.0 / 255.0, 44.0 / 255.0, 100.0 / 255.0);
+// The sun is reddish when it's low
+const HORIZON_SUN_COLOR: Color = Color::srgb(1.0, 0.6, 0.35);
+// At night the same light turns into a dim, cold moon
+const MOON_ILLUMINANCE: f32 = 400.0;
+const MOON_COLOR: Color = Color::srgb(0.6, 0.7, 1.0);
+const NIGHT_AMBIENT_FACTOR: f32 = 0.25;
+// Environment map strength at noon and at night
+const DAY_ENVIRONMENT_INTENSITY: f32 = 2000.0;
+const NIGHT_ENVIRONMENT_INTENSITY: f32 = 300.0;
+// Limits for changing the day length with the debug keys
+const MIN_DAY_LENGTH: f32 = 30.0;
+const MAX_DAY_LENGTH: f32 = 3600.0;
+
+// The level's main directional light
+#[derive(Component)]
+pub struct Sun;
+
+// Clock driving the sun, sky, fog and aurora
+#[derive(Resource)]
+pub struct TimeOfDay {
+    // 0-24, the sun rises at 6 and sets at 18
+    pub hour: f32,
+    // Real seconds one full day takes
+    pub day_length: f32,
+    pub paused: bool,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hour: 9.0,
+            day_length: 600.0,
+            paused: false,
+        }
+    }
+}
+
+impl TimeOfDay {
+    // Angle of the sun over the horizon in radians, negative at night
+    pub fn sun_elevation(&self) -> f32 {
+        ((self.hour - 6.0) / 12.0 * PI).sin() * PI / 2.0
+    }
+
+    // 0 at night, 1 once the sun is well over the horizon, with a short dusk and dawn in between
+    pub fn daylight(&self) -> f32 {
+        (self.sun_elevation() * 4.0 + 0.3).clamp(0.0, 1.0)
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.daylight() <= 0.0
+    }
+}
+
+fn mix_colors(from: Color, to: Color, t: f32) -> Color {
+    LinearRgba::from(from).mix(&LinearRgba::from(to), t).into()
+}
 
 // Spawn lighting for the scene
 fn spawn_lighting(
@@ -16,29 +82,142 @@ fn spawn_lighting(
         ..default()
     }
     .build();
-    
+
+    // Color, strength and direction come from the level manifest and the time of day,
+    // see update_sun
     commands.spawn((
         DirectionalLight {
-            illuminance: 15000.0,
             shadows_enabled: true,
             ..default()
         },
         cascade_shadow_config,
-        Transform::from_rotation(Quat::from_euler(
-            EulerRot::XYZ,
-            -std::f32::consts::FRAC_PI_4,
-            std::f32::consts::FRAC_PI_4,
-            0.0,
-        )),
+        Transform::default(),
+        Sun,
     ));
 
 
 }
 
+// Levels can start at a given hour, e.g. dungeons always at night
+fn apply_level_start_hour(
+    manifest: Res<LevelManifest>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    if let Some(hour) = manifest.lighting.start_hour {
+        time_of_day.hour = hour.rem_euclid(24.0);
+    }
+}
+
+fn advance_time_of_day(
+    mut time_of_day: ResMut<TimeOfDay>,
+    time: Res<Time>,
+) {
+    if time_of_day.paused {
+        return;
+    }
+    let hours_per_second = 24.0 / time_of_day.day_length;
+    time_of_day.hour = (time_of_day.hour + time.delta_secs() * hours_per_second).rem_euclid(24.0);
+}
+
+// Debug keys: F2 pauses the clock, F3 and F4 make days twice as long or short
+fn time_of_day_controls(
+    actions: Res<ActionState>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    if actions.just_pressed(Action::DebugPauseTime) {
+        time_of_day.paused = !time_of_day.paused;
+        info!("Time of day {} at {:.1}h", if time_of_day.paused { "paused" } else { "running" }, time_of_day.hour);
+    }
+    if actions.just_pressed(Action::DebugLongerDays) {
+        time_of_day.day_length = (time_of_day.day_length * 2.0).min(MAX_DAY_LENGTH);
+        info!("Day length: {}s", time_of_day.day_length);
+    }
+    if actions.just_pressed(Action::DebugShorterDays) {
+        time_of_day.day_length = (time_of_day.day_length * 0.5).max(MIN_DAY_LENGTH);
+        info!("Day length: {}s", time_of_day.day_length);
+    }
+}
+
+fn update_sun(
+    time_of_day: Res<TimeOfDay>,
+    manifest: Res<LevelManifest>,
+    mut sun_query: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+    mut ambient_light: ResMut<AmbientLight>,
+) {
+    let lighting = &manifest.lighting;
+    let daylight = time_of_day.daylight();
+    let elevation = time_of_day.sun_elevation();
+
+    for (mut light, mut transform) in &mut sun_query {
+        let [r, g, b] = lighting.sun_color;
+        let noon_color = Color::srgb(r, g, b);
+        let sun_color = mix_colors(HORIZON_SUN_COLOR, noon_color, (elevation / (PI / 4.0)).clamp(0.0, 1.0));
+
+        light.color = mix_colors(MOON_COLOR, sun_color, daylight);
+        light.illuminance = MOON_ILLUMINANCE.lerp(lighting.sun_illuminance, daylight);
+        light.shadows_enabled = lighting.shadows;
+        // The sun turns half a circle from sunrise at 6 to sunset at 18 and is due south at noon,
+        // sun_rotation.y turns the whole path to fit the level
+        let azimuth = lighting.sun_rotation.y.to_radians() + (time_of_day.hour - 12.0) / 12.0 * PI;
+        // At night the light is the moon, on the opposite side of the sky from the sun
+        let (azimuth, pitch) = if elevation >= 0.0 { (azimuth, elevation) } else { (azimuth + PI, -elevation) };
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, azimuth, -pitch.max(0.05), 0.0);
+    }
+
+    let [r, g, b] = lighting.ambient_color;
+    ambient_light.color = Color::srgb(r, g, b);
+    ambient_light.brightness = lighting.ambient_brightness * NIGHT_AMBIENT_FACTOR.lerp(1.0, daylight);
+}
+
+pub fn update_sky(
+    time_of_day: Res<TimeOfDay>,
+    manifest: Res<LevelManifest>,
+    mut clear_color: ResMut<ClearColor>,
+    mut cameras: Query<(&mut DistanceFog, Option<&mut EnvironmentMapLight>)>,
+    mut aurora_query: Query<(&MeshMaterial3d<AuroraShaderMaterial>, &mut Visibility)>,
+    mut aurora_materials: ResMut<Assets<AuroraShaderMaterial>>,
+) {
+    let daylight = time_of_day.daylight();
+
+    let [r, g, b] = manifest.sky_color;
+    clear_color.0 = mix_colors(Color::srgb(r, g, b), DAY_SKY_COLOR, daylight);
+
+    for (mut fog, environment_map) in &mut cameras {
+        fog.color = mix_colors(NIGHT_FOG_COLOR, DAY_FOG_COLOR, daylight);
+        if let Some(mut environment_map) = environment_map {
+            environment_map.intensity = NIGHT_ENVIRONMENT_INTENSITY.lerp(DAY_ENVIRONMENT_INTENSITY, daylight);
+        }
+    }
+
+    // The aurora only shows up at night
+    let night_fade = 1.0 - daylight;
+    for (material, mut visibility) in &mut aurora_query {
+        *visibility = if night_fade > 0.0 { Visibility::Inherited } else { Visibility::Hidden };
+        // get_mut marks the material as changed, so only touch it when the fade moved
+        let unchanged = aurora_materials.get(&material.0).is_some_and(|aurora| aurora.night_fade == night_fade);
+        if unchanged {
+            continue;
+        }
+        if let Some(aurora) = aurora_materials.get_mut(&material.0) {
+            aurora.night_fade = night_fade;
+        }
+    }
+}
+
 pub struct LightingPlugin;
 
 impl Plugin for LightingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_lighting);
+        app.init_resource::<TimeOfDay>()
+            // Set a dark sky color
+            .insert_resource(ClearColor(Color::srgb(0.05, 0.08, 0.15)))
+            .add_systems(Startup, spawn_lighting)
+            .add_systems(Update, (
+                apply_level_start_hour.run_if(resource_changed::<LevelManifest>),
+                time_of_day_controls,
+                advance_time_of_day,
+                update_sun,
+                update_sky,
+            ).chain());
     }
-}
\ No newline at end of file
+}
@@ -17,11 +17,29 @@ mod shader;
 mod progression;
 mod achievements;
 mod npcs;
+mod triggers;
+mod platforms;
+mod water;
+mod destructibles;
+mod dungeon;
+mod transitions;
+mod weather;
+mod terrain;
+mod scatter;
+mod interactables;
+mod pickups;
+mod checkpoints;
+mod navigation;
+mod input;
+mod death;
+mod enemies;
 
 fn main() {
     println!("Starting Third-Person Example...");
     println!("Controls:");
     println!("  - ESC: Exit game");
+    println!("Pass --level <name> to load assets/levels/<name>.json");
+    println!("Key bindings are in config/input.json");
     
     App::new()
         .add_plugins(DefaultPlugins
@@ -48,6 +66,7 @@ fn main() {
                 cluster_buffer_slots: 8192,
             },
             physics::AvPhysicsPlugin,
+            input::InputPlugin,
             player::PlayerPlugin,
             camera::CameraPlugin,
             world::WorldPlugin,
@@ -61,5 +80,22 @@ fn main() {
             achievements::AchievementsPlugin,
             npcs::NpcsPlugin,
         ))
+        // Level gameplay built on the physics extras
+        .add_plugins((
+            triggers::TriggersPlugin,
+            platforms::PlatformsPlugin,
+            water::WaterPlugin,
+            destructibles::DestructiblesPlugin,
+            dungeon::DungeonPlugin,
+            transitions::TransitionsPlugin,
+            weather::WeatherPlugin,
+            scatter::ScatterPlugin,
+            interactables::InteractablesPlugin,
+            pickups::PickupsPlugin,
+            checkpoints::CheckpointsPlugin,
+            navigation::NavigationPlugin,
+            death::DeathPlugin,
+            enemies::EnemiesPlugin,
+        ))
         .run();
 }
\ No newline at end of file
@@ -0,0 +1,713 @@
+use avian3d::prelude::{Collider, ColliderAabb, ColliderParent, RigidBody, SpatialQuery, SpatialQueryFilter};
+use bevy::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ops::Range;
+use std::time::Instant;
+
+use crate::input::{Action, ActionState};
+use crate::physics::{GameLayer, LevelSpawned};
+use crate::player::Player;
+use crate::world::LevelManifest;
+
+// Seconds to wait after a level spawned before sampling it, so colliders built from meshes
+// exist and the spatial query pipeline has caught up
+const BUILD_DELAY: f32 = 0.5;
+// Floors stacked over each other that are sampled per column
+const MAX_LAYERS: usize = 4;
+// Columns sampled per frame, a whole level takes a few dozen frames instead of one long hitch
+const COLUMNS_PER_FRAME: usize = 1024;
+// Levels that would need more columns are sampled with bigger cells
+const MAX_COLUMNS: usize = 256 * 256;
+// How many cells around a point are searched for the closest node
+const NEAREST_SEARCH_CELLS: i32 = 4;
+// Only nodes this close to the player are drawn by the debug view
+const DEBUG_DRAW_RADIUS: f32 = 15.0;
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavMesh>()
+            .init_resource::<PendingNavMeshBuild>()
+            .init_resource::<NavMeshBuild>()
+            .add_event::<NavMeshBuilt>()
+            .add_systems(Update, (
+                queue_navmesh_build,
+                start_navmesh_build,
+                sample_navmesh,
+                navmesh_debug_controls,
+                draw_navmesh,
+            ).chain());
+    }
+}
+
+// Size of the agents the navmesh is built for, the defaults match the player capsule
+#[derive(Debug, Clone)]
+pub struct NavMeshSettings {
+    pub agent_radius: f32,
+    pub agent_height: f32,
+    // Highest ledge an agent walks up without jumping
+    pub step_height: f32,
+    // Steepest walkable ground in degrees
+    pub max_slope: f32,
+    // Distance between samples, raised for big levels, see MAX_COLUMNS
+    pub cell_size: f32,
+    // Sampling stops this far from the origin, half-space ground planes have no real bounds
+    pub max_extent: f32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            agent_radius: 0.3,
+            agent_height: 1.5,
+            step_height: 0.4,
+            max_slope: 45.0,
+            cell_size: 0.5,
+            max_extent: 100.0,
+        }
+    }
+}
+
+struct NavNode {
+    position: Vec3,
+    neighbours: Vec<usize>,
+}
+
+// Walkable points sampled on a grid over the static level geometry, with a node for every
+// floor in a column and links between nodes an agent can walk between
+#[derive(Resource)]
+pub struct NavMesh {
+    pub settings: NavMeshSettings,
+    // Distance between samples actually used
+    cell_size: f32,
+    min: Vec2,
+    width: usize,
+    depth: usize,
+    // Node indices of every cell, indexed x * depth + z
+    cells: Vec<Vec<usize>>,
+    nodes: Vec<NavNode>,
+    // Static geometry the navmesh was built from
+    geometry: Option<GeometryKey>,
+    pub debug_draw: bool,
+}
+
+#[derive(Event)]
+pub struct NavMeshBuilt {
+    pub nodes: usize,
+}
+
+// Seconds left until the navmesh is rebuilt
+#[derive(Resource, Default)]
+struct PendingNavMeshBuild {
+    delay: Option<f32>,
+    // Rebuild even if the static geometry looks the same, e.g. after a hot reload
+    force: bool,
+}
+
+// Navmesh being sampled a few columns per frame, it replaces the NavMesh resource once done so
+// agents keep using the old one until then
+#[derive(Resource, Default)]
+struct NavMeshBuild(Option<NavMeshSampler>);
+
+struct NavMeshSampler {
+    navmesh: NavMesh,
+    bottom: f32,
+    top: f32,
+    next_column: usize,
+    started: Instant,
+}
+
+// Rough fingerprint of the static colliders. Respawning reloads the same level, when nothing
+// static changed there's no need to sample it again.
+#[derive(Debug, Clone, Copy)]
+struct GeometryKey {
+    colliders: usize,
+    min: Vec3,
+    max: Vec3,
+    // Sum of the collider centers, catches geometry that moved inside the bounds
+    centers: Vec3,
+}
+
+impl GeometryKey {
+    fn matches(&self, other: &GeometryKey) -> bool {
+        self.colliders == other.colliders
+            && self.min.abs_diff_eq(other.min, 0.01)
+            && self.max.abs_diff_eq(other.max, 0.01)
+            && self.centers.abs_diff_eq(other.centers, 0.01 * self.colliders as f32)
+    }
+}
+
+// A* open set entry, ordered so the cheapest node comes out of the heap first
+struct OpenNode {
+    estimate: f32,
+    node: usize,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl Default for NavMesh {
+    fn default() -> Self {
+        Self::new(NavMeshSettings::default(), Vec3::ZERO, Vec3::ZERO)
+    }
+}
+
+impl NavMesh {
+    // Empty grid covering min to max, filled in by sample_columns
+    pub fn new(settings: NavMeshSettings, min: Vec3, max: Vec3) -> Self {
+        let columns = |cell: f32| {
+            (((max.x - min.x) / cell).ceil() as usize + 1) * (((max.z - min.z) / cell).ceil() as usize + 1)
+        };
+        let mut cell = settings.cell_size;
+        while columns(cell) > MAX_COLUMNS {
+            cell *= 1.25;
+        }
+        if cell != settings.cell_size {
+            warn!(
+                "Level is {:.0}x{:.0}m, sampling the navmesh every {:.2}m instead of {:.2}m",
+                max.x - min.x,
+                max.z - min.z,
+                cell,
+                settings.cell_size
+            );
+        }
+
+        let width = ((max.x - min.x) / cell).ceil() as usize + 1;
+        let depth = ((max.z - min.z) / cell).ceil() as usize + 1;
+        Self {
+            settings,
+            cell_size: cell,
+            min: Vec2::new(min.x, min.z),
+            width,
+            depth,
+            cells: vec![Vec::new(); width * depth],
+            nodes: Vec::new(),
+            geometry: None,
+            debug_draw: false,
+        }
+    }
+
+    fn column_count(&self) -> usize {
+        self.width * self.depth
+    }
+
+    fn add_node(&mut self, x: usize, z: usize, position: Vec3) {
+        self.cells[x * self.depth + z].push(self.nodes.len());
+        self.nodes.push(NavNode {
+            position,
+            neighbours: Vec::new(),
+        });
+    }
+
+    // Sample the static colliders between bottom and top in the given columns, is_static filters
+    // out everything that moves
+    fn sample_columns(
+        &mut self,
+        columns: Range<usize>,
+        bottom: f32,
+        top: f32,
+        spatial_query: &SpatialQuery,
+        is_static: &dyn Fn(Entity) -> bool,
+    ) {
+        let settings = &self.settings;
+        let filter = SpatialQueryFilter::from_mask(GameLayer::Environment);
+        let min_normal_y = settings.max_slope.to_radians().cos();
+
+        // Capsule filling the space an agent needs above the ground, starting at step height so
+        // the ground itself and small bumps don't count
+        let clearance = settings.agent_height - settings.step_height;
+        let capsule_length = (clearance - 2.0 * settings.agent_radius).max(0.0);
+        let capsule = Collider::capsule(settings.agent_radius, capsule_length);
+        let capsule_height = settings.step_height + clearance * 0.5;
+        let blocked = |point: Vec3| {
+            let center = point + Vec3::Y * capsule_height;
+            spatial_query
+                .shape_intersections(&capsule, center, Quat::IDENTITY, &filter)
+                .into_iter()
+                .any(is_static)
+        };
+
+        for index in columns {
+            let (x, z) = (index / self.depth, index % self.depth);
+            let column = self.cell_center(x, z);
+            let mut top = top;
+            // Rays that start inside a floor hit its underside, so keep casting below each
+            // hit to find the floors underneath
+            for _ in 0..MAX_LAYERS * 2 {
+                let origin = Vec3::new(column.x, top, column.y);
+                let Some(hit) = spatial_query.cast_ray_predicate(
+                    origin,
+                    Dir3::NEG_Y,
+                    top - bottom + 1.0,
+                    false,
+                    &filter,
+                    is_static,
+                ) else {
+                    break;
+                };
+                let point = origin - Vec3::Y * hit.distance;
+                if hit.normal.y >= min_normal_y && !blocked(point) {
+                    self.add_node(x, z, point);
+                    if self.cells[index].len() >= MAX_LAYERS {
+                        break;
+                    }
+                }
+                top = point.y - 0.01;
+                if top < bottom {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn cell_center(&self, x: usize, z: usize) -> Vec2 {
+        self.min + Vec2::new(x as f32, z as f32) * self.cell_size
+    }
+
+    fn cell_of(&self, point: Vec3) -> Option<(usize, usize)> {
+        let cell = ((Vec2::new(point.x, point.z) - self.min) / self.cell_size).round();
+        if cell.x < 0.0 || cell.y < 0.0 || cell.x as usize >= self.width || cell.y as usize >= self.depth {
+            return None;
+        }
+        Some((cell.x as usize, cell.y as usize))
+    }
+
+    // Height difference an agent can cover over the given horizontal distance
+    fn max_climb(&self, distance: f32) -> f32 {
+        self.settings.step_height.max(distance * self.settings.max_slope.to_radians().tan())
+    }
+
+    // Node of the given cell an agent at position can walk onto
+    fn reachable_node(&self, position: Vec3, x: i32, z: i32) -> Option<usize> {
+        if x < 0 || z < 0 || x as usize >= self.width || z as usize >= self.depth {
+            return None;
+        }
+        let (x, z) = (x as usize, z as usize);
+        let distance = self.cell_center(x, z).distance(Vec2::new(position.x, position.z));
+        let max_climb = self.max_climb(distance);
+        self.cells[x * self.depth + z]
+            .iter()
+            .copied()
+            .map(|node| (node, (self.nodes[node].position.y - position.y).abs()))
+            .filter(|(_, climb)| *climb <= max_climb)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(node, _)| node)
+    }
+
+    fn link_nodes(&mut self) {
+        for x in 0..self.width {
+            for z in 0..self.depth {
+                for &node in &self.cells[x * self.depth + z].clone() {
+                    let position = self.nodes[node].position;
+                    let (x, z) = (x as i32, z as i32);
+                    let mut neighbours = Vec::with_capacity(8);
+                    for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                        neighbours.extend(self.reachable_node(position, x + dx, z + dz));
+                    }
+                    // Diagonals only when both sides are open, so agents don't cut wall corners
+                    for (dx, dz) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                        if self.reachable_node(position, x + dx, z).is_some()
+                            && self.reachable_node(position, x, z + dz).is_some()
+                        {
+                            neighbours.extend(self.reachable_node(position, x + dx, z + dz));
+                        }
+                    }
+                    self.nodes[node].neighbours = neighbours;
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Closest walkable point to the given position, preferring the floor at the same height
+    pub fn nearest_point(&self, point: Vec3) -> Option<Vec3> {
+        self.nearest_node(point).map(|node| self.nodes[node].position)
+    }
+
+    fn nearest_node(&self, point: Vec3) -> Option<usize> {
+        let cell = ((Vec2::new(point.x, point.z) - self.min) / self.cell_size).round();
+        let (cx, cz) = (cell.x as i32, cell.y as i32);
+        let mut best: Option<(usize, f32)> = None;
+        for x in cx - NEAREST_SEARCH_CELLS..=cx + NEAREST_SEARCH_CELLS {
+            for z in cz - NEAREST_SEARCH_CELLS..=cz + NEAREST_SEARCH_CELLS {
+                if x < 0 || z < 0 || x as usize >= self.width || z as usize >= self.depth {
+                    continue;
+                }
+                for &node in &self.cells[x as usize * self.depth + z as usize] {
+                    let offset = self.nodes[node].position - point;
+                    // Being on another floor is worse than being a bit to the side
+                    let score = offset.x * offset.x + offset.z * offset.z + (offset.y * 2.0).powi(2);
+                    if best.is_none_or(|(_, best_score)| score < best_score) {
+                        best = Some((node, score));
+                    }
+                }
+            }
+        }
+        best.map(|(node, _)| node)
+    }
+
+    // Smoothed waypoints from the closest walkable point to `from` to the closest one to `to`,
+    // or None when there's no way between them
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        let start = self.nearest_node(from)?;
+        let goal = self.nearest_node(to)?;
+        let nodes = self.search(start, goal)?;
+        Some(self.smooth(&nodes))
+    }
+
+    fn search(&self, start: usize, goal: usize) -> Option<Vec<usize>> {
+        let goal_position = self.nodes[goal].position;
+        let mut costs = vec![f32::INFINITY; self.nodes.len()];
+        let mut came_from = vec![usize::MAX; self.nodes.len()];
+        let mut open = BinaryHeap::new();
+        costs[start] = 0.0;
+        open.push(OpenNode {
+            estimate: self.nodes[start].position.distance(goal_position),
+            node: start,
+        });
+
+        while let Some(OpenNode { node, estimate }) = open.pop() {
+            if node == goal {
+                let mut path = vec![goal];
+                let mut current = goal;
+                while current != start {
+                    current = came_from[current];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            // Skip heap entries that were superseded by a cheaper route
+            if estimate > costs[node] + self.nodes[node].position.distance(goal_position) + 0.001 {
+                continue;
+            }
+            for &neighbour in &self.nodes[node].neighbours {
+                let cost = costs[node] + self.nodes[node].position.distance(self.nodes[neighbour].position);
+                if cost < costs[neighbour] {
+                    costs[neighbour] = cost;
+                    came_from[neighbour] = node;
+                    open.push(OpenNode {
+                        estimate: cost + self.nodes[neighbour].position.distance(goal_position),
+                        node: neighbour,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    // Drop every waypoint the agent can skip by walking straight to a later one
+    fn smooth(&self, nodes: &[usize]) -> Vec<Vec3> {
+        let mut waypoints = vec![self.nodes[nodes[0]].position];
+        let mut current = 0;
+        while current < nodes.len() - 1 {
+            let mut next = current + 1;
+            for candidate in (current + 2..nodes.len()).rev() {
+                if self.walkable_line(nodes[current], nodes[candidate]) {
+                    next = candidate;
+                    break;
+                }
+            }
+            waypoints.push(self.nodes[nodes[next]].position);
+            current = next;
+        }
+        waypoints
+    }
+
+    // Whether there's walkable ground all along the straight line between two nodes
+    fn walkable_line(&self, from: usize, to: usize) -> bool {
+        let start = self.nodes[from].position;
+        let end = self.nodes[to].position;
+        let sample_distance = self.cell_size * 0.5;
+        let steps = (Vec2::new(end.x - start.x, end.z - start.z).length() / sample_distance).ceil() as usize;
+        let max_climb = self.max_climb(sample_distance);
+
+        let mut height = start.y;
+        for step in 1..=steps {
+            let point = start.lerp(end, step as f32 / steps as f32);
+            let Some((x, z)) = self.cell_of(point) else {
+                return false;
+            };
+            let Some(node) = self.cells[x * self.depth + z]
+                .iter()
+                .map(|&node| self.nodes[node].position.y)
+                .filter(|y| (y - height).abs() <= max_climb)
+                .min_by(|a, b| (a - height).abs().total_cmp(&(b - height).abs()))
+            else {
+                return false;
+            };
+            height = node;
+        }
+        true
+    }
+}
+
+// Rebuild whenever a level was spawned, a level glb finished loading or got hot reloaded
+fn queue_navmesh_build(
+    mut level_events: EventReader<LevelSpawned>,
+    manifest: Res<LevelManifest>,
+    mut pending: ResMut<PendingNavMeshBuild>,
+) {
+    let mut queued = manifest.is_changed();
+    for event in level_events.read() {
+        queued = true;
+        pending.force |= event.reloaded;
+    }
+    if queued {
+        pending.delay = Some(BUILD_DELAY);
+    }
+}
+
+fn start_navmesh_build(
+    time: Res<Time>,
+    mut pending: ResMut<PendingNavMeshBuild>,
+    colliders: Query<(&ColliderAabb, &ColliderParent)>,
+    bodies: Query<&RigidBody>,
+    navmesh: Res<NavMesh>,
+    mut build: ResMut<NavMeshBuild>,
+) {
+    let Some(remaining) = pending.delay else {
+        return;
+    };
+    let remaining = remaining - time.delta_secs();
+    if remaining > 0.0 {
+        pending.delay = Some(remaining);
+        return;
+    }
+    pending.delay = None;
+    let force = std::mem::take(&mut pending.force);
+
+    let settings = navmesh.settings.clone();
+    let extent = Vec3::splat(settings.max_extent);
+    let static_bounds: Vec<(Vec3, Vec3)> = colliders
+        .iter()
+        .filter(|(_, parent)| bodies.get(parent.get()).is_ok_and(|body| body.is_static()))
+        .map(|(aabb, _)| (aabb.min.clamp(-extent, extent), aabb.max.clamp(-extent, extent)))
+        .collect();
+    let Some((min, max)) = static_bounds
+        .iter()
+        .copied()
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+    else {
+        warn!("No static colliders to build a navmesh from");
+        return;
+    };
+
+    let geometry = GeometryKey {
+        colliders: static_bounds.len(),
+        min,
+        max,
+        centers: static_bounds.iter().map(|(min, max)| (*min + *max) * 0.5).sum(),
+    };
+    // A build for the same geometry that is still running counts as well
+    let current = build.0.as_ref().map_or(&navmesh.geometry, |sampler| &sampler.navmesh.geometry);
+    if !force && current.is_some_and(|current| current.matches(&geometry)) {
+        info!("Static geometry didn't change, keeping the navmesh");
+        return;
+    }
+
+    let top = max.y + settings.agent_height;
+    let mut sampler = NavMeshSampler {
+        navmesh: NavMesh::new(settings, min, max),
+        bottom: min.y,
+        top,
+        next_column: 0,
+        started: Instant::now(),
+    };
+    sampler.navmesh.geometry = Some(geometry);
+    // Replaces a build that was still running for older geometry
+    build.0 = Some(sampler);
+}
+
+fn sample_navmesh(
+    spatial_query: SpatialQuery,
+    colliders: Query<&ColliderParent>,
+    bodies: Query<&RigidBody>,
+    mut build: ResMut<NavMeshBuild>,
+    mut navmesh: ResMut<NavMesh>,
+    mut built_events: EventWriter<NavMeshBuilt>,
+) {
+    let Some(sampler) = build.0.as_mut() else {
+        return;
+    };
+    let is_static = |entity: Entity| {
+        colliders
+            .get(entity)
+            .ok()
+            .and_then(|parent| bodies.get(parent.get()).ok())
+            .is_some_and(|body| body.is_static())
+    };
+
+    let end = (sampler.next_column + COLUMNS_PER_FRAME).min(sampler.navmesh.column_count());
+    sampler
+        .navmesh
+        .sample_columns(sampler.next_column..end, sampler.bottom, sampler.top, &spatial_query, &is_static);
+    sampler.next_column = end;
+    if end < sampler.navmesh.column_count() {
+        return;
+    }
+
+    let Some(mut sampler) = build.0.take() else {
+        return;
+    };
+    sampler.navmesh.link_nodes();
+    sampler.navmesh.debug_draw = navmesh.debug_draw;
+    *navmesh = sampler.navmesh;
+    info!(
+        "Built navmesh with {} nodes over {}x{} cells in {:.0}ms",
+        navmesh.nodes.len(),
+        navmesh.width,
+        navmesh.depth,
+        sampler.started.elapsed().as_secs_f32() * 1000.0
+    );
+    built_events.send(NavMeshBuilt { nodes: navmesh.nodes.len() });
+}
+
+// Debug key: F6 shows the navmesh around the player
+fn navmesh_debug_controls(
+    actions: Res<ActionState>,
+    mut navmesh: ResMut<NavMesh>,
+) {
+    if actions.just_pressed(Action::DebugNavmesh) {
+        navmesh.debug_draw = !navmesh.debug_draw;
+        info!("Navmesh debug view {}", if navmesh.debug_draw { "on" } else { "off" });
+    }
+}
+
+fn draw_navmesh(
+    navmesh: Res<NavMesh>,
+    player_query: Query<&Transform, With<Player>>,
+    mut gizmos: Gizmos,
+) {
+    if !navmesh.debug_draw {
+        return;
+    }
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let lift = Vec3::Y * 0.05;
+    for node in &navmesh.nodes {
+        if node.position.distance(player.translation) > DEBUG_DRAW_RADIUS {
+            continue;
+        }
+        for &neighbour in &node.neighbours {
+            gizmos.line(node.position + lift, navmesh.nodes[neighbour].position + lift, Color::srgb(0.2, 0.8, 1.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Flat ground with a node in every cell of a width x depth grid except the walls, cells are
+    // half a meter apart starting at the origin
+    fn flat_navmesh(width: usize, depth: usize, walls: &[(usize, usize)]) -> NavMesh {
+        let settings = NavMeshSettings::default();
+        let max = Vec3::new((width - 1) as f32, 0.0, (depth - 1) as f32) * settings.cell_size;
+        let mut navmesh = NavMesh::new(settings, Vec3::ZERO, max);
+        for x in 0..width {
+            for z in 0..depth {
+                if walls.contains(&(x, z)) {
+                    continue;
+                }
+                let center = navmesh.cell_center(x, z);
+                navmesh.add_node(x, z, Vec3::new(center.x, 0.0, center.y));
+            }
+        }
+        navmesh.link_nodes();
+        navmesh
+    }
+
+    fn wall_at_x(x: usize, zs: Range<usize>) -> Vec<(usize, usize)> {
+        zs.map(|z| (x, z)).collect()
+    }
+
+    #[test]
+    fn open_ground_is_a_straight_line() {
+        let navmesh = flat_navmesh(10, 10, &[]);
+        let path = navmesh.find_path(Vec3::ZERO, Vec3::new(4.5, 0.0, 3.0)).unwrap();
+        assert_eq!(path, vec![Vec3::ZERO, Vec3::new(4.5, 0.0, 3.0)]);
+    }
+
+    #[test]
+    fn path_goes_around_a_wall() {
+        // Wall across the middle with a gap at the far end
+        let navmesh = flat_navmesh(11, 11, &wall_at_x(5, 0..9));
+        let path = navmesh.find_path(Vec3::new(0.5, 0.0, 0.5), Vec3::new(4.5, 0.0, 0.5)).unwrap();
+        assert_eq!(path.first(), Some(&Vec3::new(0.5, 0.0, 0.5)));
+        assert_eq!(path.last(), Some(&Vec3::new(4.5, 0.0, 0.5)));
+        assert!(path.len() > 2, "{:?}", path);
+        // Has to pass through the gap at z >= 4.5
+        assert!(path.iter().any(|point| point.z >= 4.5), "{:?}", path);
+    }
+
+    #[test]
+    fn no_path_through_a_closed_wall() {
+        let navmesh = flat_navmesh(11, 11, &wall_at_x(5, 0..11));
+        assert!(navmesh.find_path(Vec3::new(0.5, 0.0, 0.5), Vec3::new(4.5, 0.0, 0.5)).is_none());
+    }
+
+    #[test]
+    fn ledges_higher_than_a_step_are_not_linked() {
+        let mut navmesh = NavMesh::new(NavMeshSettings::default(), Vec3::ZERO, Vec3::new(0.5, 1.0, 0.0));
+        navmesh.add_node(0, 0, Vec3::ZERO);
+        navmesh.add_node(1, 0, Vec3::new(0.5, 1.0, 0.0));
+        navmesh.link_nodes();
+        assert!(navmesh.find_path(Vec3::ZERO, Vec3::new(0.5, 1.0, 0.0)).is_none());
+
+        let mut navmesh = NavMesh::new(NavMeshSettings::default(), Vec3::ZERO, Vec3::new(0.5, 0.3, 0.0));
+        navmesh.add_node(0, 0, Vec3::ZERO);
+        navmesh.add_node(1, 0, Vec3::new(0.5, 0.3, 0.0));
+        navmesh.link_nodes();
+        assert!(navmesh.find_path(Vec3::ZERO, Vec3::new(0.5, 0.3, 0.0)).is_some());
+    }
+
+    #[test]
+    fn smoothing_only_keeps_the_corners() {
+        let navmesh = flat_navmesh(11, 11, &wall_at_x(5, 0..9));
+        let start = navmesh.nearest_node(Vec3::new(0.5, 0.0, 0.5)).unwrap();
+        let goal = navmesh.nearest_node(Vec3::new(4.5, 0.0, 0.5)).unwrap();
+        let nodes = navmesh.search(start, goal).unwrap();
+        let waypoints = navmesh.smooth(&nodes);
+
+        assert!(waypoints.len() < nodes.len(), "{} waypoints for {} nodes", waypoints.len(), nodes.len());
+        assert_eq!(waypoints[0], navmesh.nodes[start].position);
+        assert_eq!(*waypoints.last().unwrap(), navmesh.nodes[goal].position);
+        // Every shortcut it took has to be walkable
+        for pair in waypoints.windows(2) {
+            let from = navmesh.nearest_node(pair[0]).unwrap();
+            let to = navmesh.nearest_node(pair[1]).unwrap();
+            assert!(navmesh.walkable_line(from, to), "{:?} -> {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn huge_levels_use_bigger_cells() {
+        let navmesh = NavMesh::new(NavMeshSettings::default(), Vec3::splat(-500.0), Vec3::splat(500.0));
+        assert!(navmesh.column_count() <= MAX_COLUMNS);
+        assert!(navmesh.cell_size > navmesh.settings.cell_size);
+    }
+}
@@ -1,44 +1,57 @@
 use bevy::prelude::*;
 use avian3d::prelude::{Collider, RigidBody};
-use bevy::input::keyboard::KeyCode;
 use std::time::Duration;
 
+use crate::input::{Action, ActionState};
+use crate::interactables::{InteractEvent, Interactable, InteractionFocus};
+use crate::physics::{BMarker, GameLayer, LevelMarker};
 use crate::player::Player;
 use crate::progression::{StatAllocationEvent, PlayerProgress};
+use crate::world::{LevelEntity, LevelManifest};
 
 pub struct NpcsPlugin;
 
 impl Plugin for NpcsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_npcs)
-           .add_systems(Update, (
+        app.add_systems(Update, (
+                spawn_default_station,
+                spawn_npcs_at_markers.after(spawn_default_station),
                 handle_npc_interaction,
-                update_interaction_prompt,
+                leave_stations,
                 handle_feedback_text,
                 debug_gain_souls, // Add debug system to gain souls with a key
             ));
     }
 }
 
-#[derive(Component)]
+// The first use of a station rests and sets the respawn point, see checkpoints.rs. While the
+// player stays rested there, using it again levels up.
+#[derive(Component, Default)]
 pub struct LevelUpStation {
-    pub interaction_range: f32,
-    pub can_interact: bool,
+    pub rested: bool,
 }
 
-#[derive(Component)]
-pub struct InteractionPrompt;
+pub const STATION_REST_PROMPT: &str = "Press E to rest";
+pub const STATION_LEVEL_UP_PROMPT: &str = "Press E to level up";
+
+const STATION_INTERACTION_RANGE: f32 = 3.0;
+
+// Where the station goes when the level doesn't place one
+const DEFAULT_STATION_POSITION: Vec3 = Vec3::new(10.0, 0.0, 10.0);
 
-fn create_prompt_text() -> Text {
-    "Press E to level up".into()
+// Generic npc spawned at NpcSpawn markers
+#[derive(Component)]
+pub struct Npc {
+    pub id: String,
 }
 
-fn spawn_npcs(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    // Create a level-up station at the edge of the map
+// Spawn a level-up station standing on the given ground position
+fn spawn_level_up_station(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    position: Vec3,
+) -> Entity {
     let level_station_mesh = meshes.add(Cylinder::new(1.0, 3.0));
     let level_station_material = materials.add(StandardMaterial {
         base_color: Color::srgb(0.5, 0.0, 0.5), // Purple
@@ -53,57 +66,66 @@ fn spawn_npcs(
         Name::new("Level Up Station"),
         Mesh3d(level_station_mesh),
         MeshMaterial3d(level_station_material),
-        Transform::from_xyz(10.0, 1.5, 10.0),
+        // The cylinder is centered, lift it so it stands on the marker
+        Transform::from_translation(position + Vec3::Y * 1.5),
         RigidBody::Static,
         Collider::cylinder(1.0, 1.5),
-        LevelUpStation {
-            interaction_range: 3.0,
-            can_interact: false,
-        },
-    ));
-    
-    // Create interaction prompt text following the pattern from ui.rs
-    commands.spawn((
-        create_prompt_text(),
-        Node {
-            position_type: PositionType::Absolute,
-            top: Val::Px(100.0),
-            left: Val::Percent(50.0),
-            display: Display::None, // Hidden by default
-            ..default()
-        },
-        InteractionPrompt,
-    ));
+        GameLayer::npc(),
+        LevelUpStation::default(),
+        Interactable::new(STATION_REST_PROMPT, STATION_INTERACTION_RANGE),
+    )).id()
 }
 
-// System to check if player is in range of the level up station
-fn update_interaction_prompt(
-    mut level_stations: Query<(&Transform, &mut LevelUpStation)>,
-    player_query: Query<&Transform, With<Player>>,
-    mut prompt_query: Query<&mut Node, With<InteractionPrompt>>,
+// The hardcoded station of levels that don't place one, replaced if a Station marker shows up
+#[derive(Component)]
+struct DefaultStation;
+
+// Stations and npcs are children of their marker like enemies, so they go away with the level
+// whether the marker came from a glb scene or from the manifest
+fn spawn_npcs_at_markers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    markers: Query<(Entity, &LevelMarker), Added<LevelMarker>>,
+    default_stations: Query<Entity, With<DefaultStation>>,
 ) {
-    if let Ok(player_transform) = player_query.get_single() {
-        for (station_transform, mut station) in &mut level_stations {
-            let distance = player_transform.translation.distance(station_transform.translation);
-            
-            // Check if player is in range
-            if distance <= station.interaction_range {
-                station.can_interact = true;
-                
-                // Show interaction prompt
-                if let Ok(mut node) = prompt_query.get_single_mut() {
-                    node.display = Display::Flex;
-                }
-            } else {
-                station.can_interact = false;
-                
-                // Hide interaction prompt
-                if let Ok(mut node) = prompt_query.get_single_mut() {
-                    node.display = Display::None;
+    for (entity, marker) in &markers {
+        let spawned = match &marker.0 {
+            BMarker::Station => {
+                for station in &default_stations {
+                    commands.entity(station).despawn_recursive();
                 }
+                spawn_level_up_station(&mut commands, &mut meshes, &mut materials, Vec3::ZERO)
             }
-        }
+            BMarker::NpcSpawn { id } => commands.spawn((
+                Name::new(format!("Npc {}", id)),
+                Mesh3d(meshes.add(Capsule3d::new(0.3, 1.0))),
+                MeshMaterial3d(materials.add(Color::srgb(0.6, 0.6, 0.5))),
+                Transform::from_translation(Vec3::Y * 0.8),
+                RigidBody::Static,
+                Collider::capsule(0.3, 1.0),
+                GameLayer::npc(),
+                Npc { id: id.clone() },
+            )).id(),
+            _ => continue,
+        };
+        commands.entity(entity).add_child(spawned);
+    }
+}
+
+// Levels without a station marker still get the old hardcoded one. It is spawned with the
+// manifest, spawn_npcs_at_markers removes it again if one of the level's glb scenes has a station.
+fn spawn_default_station(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    manifest: Res<LevelManifest>,
+) {
+    if !manifest.is_changed() || manifest.markers.iter().any(|entry| entry.marker == BMarker::Station) {
+        return;
     }
+    let station = spawn_level_up_station(&mut commands, &mut meshes, &mut materials, DEFAULT_STATION_POSITION);
+    commands.entity(station).insert((DefaultStation, LevelEntity));
 }
 
 // Define the cost in souls for leveling up
@@ -118,11 +140,33 @@ pub struct LevelUpFeedback {
     timer: Timer,
 }
 
+impl LevelUpFeedback {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+}
+
+// Message in the middle of the screen, top pixels down, that goes away after a few seconds
+pub fn spawn_feedback_text(commands: &mut Commands, text: impl Into<String>, top: f32, seconds: f32) {
+    commands.spawn((
+        Text::new(text),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(top),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+        LevelUpFeedback::new(seconds),
+    ));
+}
+
 
 // System to handle interaction with the level up station
-fn handle_npc_interaction(
+pub fn handle_npc_interaction(
     level_stations: Query<&LevelUpStation>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+    mut interact_events: EventReader<InteractEvent>,
     mut player_progress: ResMut<PlayerProgress>,
     mut player_query: Query<&mut Player>,
     mut stat_events: EventWriter<StatAllocationEvent>,
@@ -130,10 +174,13 @@ fn handle_npc_interaction(
     mut game_ui: ResMut<crate::ui::GameUI>,
     _time: Res<Time>,
 ) {
-    // Check if any level station is in range
-    let can_interact = level_stations.iter().any(|station| station.can_interact);
+    // Only interactions with a level station the player already rested at, resting is handled
+    // in checkpoints.rs and doors and chests in interactables.rs
+    let used_station = interact_events
+        .read()
+        .any(|event| level_stations.get(event.entity).is_ok_and(|station| station.rested));
     
-    if can_interact && keyboard.just_pressed(KeyCode::KeyE) {
+    if used_station {
         // Check if player has enough souls to level up
         if game_ui.souls >= LEVEL_UP_COST as usize {
             // Consume souls for leveling up
@@ -294,6 +341,19 @@ fn handle_npc_interaction(
     }
 }
 
+// Walking away from a station ends the rest, the next use rests again
+fn leave_stations(
+    focus: Res<InteractionFocus>,
+    mut stations: Query<(Entity, &mut LevelUpStation, &mut Interactable)>,
+) {
+    for (entity, mut station, mut interactable) in &mut stations {
+        if station.rested && focus.0 != Some(entity) {
+            station.rested = false;
+            interactable.prompt = STATION_REST_PROMPT.to_string();
+        }
+    }
+}
+
 // System to handle the feedback text lifetime
 fn handle_feedback_text(
     mut commands: Commands,
@@ -312,11 +372,11 @@ fn handle_feedback_text(
 
 // DEBUG: System to gain souls with G key for testing
 fn debug_gain_souls(
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut game_ui: ResMut<crate::ui::GameUI>,
     mut commands: Commands,
 ) {
-    if keyboard.just_pressed(KeyCode::KeyG) {
+    if actions.just_pressed(Action::DebugGainSouls) {
         // Add 100 souls for testing
         game_ui.souls += 100;
         
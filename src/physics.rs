@@ -1,39 +1,652 @@
 use avian3d::prelude::{
       Collider,
       ColliderConstructor,
+      DistanceJoint,
+      FixedJoint,
+      Joint,
+      PrismaticJoint,
+      RevoluteJoint,
+      CollisionLayers,
+      Friction,
+      LayerMask,
+      Mass,
+      Restitution,
       RigidBody,
+      Sensor,
       PhysicsDebugPlugin,
       PhysicsPlugins,
       Gravity,
+      PhysicsLayer,
 };
 use bevy::{
       prelude::*,
       app::{App, Plugin},
       ecs::system::Commands, math::Vec3,
-      gltf::GltfMeshExtras, scene::SceneInstanceReady, 
+      gltf::{GltfExtras, GltfMeshExtras}, scene::SceneInstanceReady, 
 };
 use bevy_tnua::prelude::*;
 use bevy_tnua_avian3d::*;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::destructibles::Destructible;
+use crate::interactables::insert_interactable;
+use crate::platforms::MovingPlatform;
+use crate::scatter::ScatterSurface;
+use crate::triggers::TriggerVolume;
+use crate::water::WaterVolume;
 
 /// Extras for physics
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BMeshExtras {
+    #[serde(default)]
     pub collider: BCollider,
+    #[serde(default)]
     pub rigid_body: BRigidBody,
     pub cube_size: Option<Vec3>,
+    // Used by the Sphere, Capsule and Cylinder colliders
+    #[serde(default = "default_radius")]
+    pub radius: f32,
+    #[serde(default = "default_height")]
+    pub height: f32,
+    // Optional body properties, avian's defaults are used when omitted
+    pub mass: Option<f32>,
+    pub friction: Option<f32>,
+    pub restitution: Option<f32>,
+    // Turns the collider into a sensor volume, see triggers.rs
+    pub trigger: Option<BTrigger>,
+    // Collision layers this node is in and the ones it collides with,
+    // defaults to GameLayer::environment() or GameLayer::trigger()
+    pub layers: Option<Vec<GameLayer>>,
+    pub collides_with: Option<Vec<GameLayer>>,
+    // Connects this body to another node of the same level
+    pub joint: Option<BJoint>,
+    // Makes a kinematic body follow a path, see platforms.rs
+    pub path: Option<BPath>,
+    // Turns the collider into a water volume, see water.rs
+    pub water: Option<BWater>,
+    // Breaks into debris once it took enough damage, see destructibles.rs
+    pub destructible: Option<BDestructible>,
+    // Scatter layers with this surface tag place foliage on the mesh, see scatter.rs
+    pub scatter_surface: Option<String>,
+    // Door, lever or chest the player can use, see interactables.rs
+    pub interactable: Option<BInteractable>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn default_radius() -> f32 {
+    0.5
+}
+
+fn default_height() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub enum BCollider {
+    #[default]
     TrimeshFromMesh,
+    // Use this one for dynamic bodies, trimeshes are only good for static geometry
+    ConvexHullFromMesh,
+    #[serde(alias = "Cuboid")]
     Cubiod,
+    Sphere,
+    Capsule,
+    Cylinder,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub enum BRigidBody {
+    #[default]
     Static,
     Dynamic,
+    Kinematic,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BDestructible {
+    #[serde(default = "default_destructible_health")]
+    pub health: f32,
+    // Scene with the broken pieces, e.g. "models/crate_broken.glb#Scene0".
+    // Generic chunks are spawned when omitted.
+    pub fracture_scene: Option<String>,
+    // Seconds before the debris despawns
+    #[serde(default = "default_debris_lifetime")]
+    pub debris_lifetime: f32,
+}
+
+fn default_destructible_health() -> f32 {
+    50.0
+}
+
+fn default_debris_lifetime() -> f32 {
+    5.0
+}
+
+// Things used with the interact key. The id is what levers point at and what the
+// open/closed state is saved under, so it has to be unique within a level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BInteractable {
+    Door {
+        id: String,
+        #[serde(default)]
+        open: bool,
+        // Locked doors can only be opened by a lever
+        #[serde(default)]
+        locked: bool,
+        // How far the door swings open around the Y axis, in degrees
+        #[serde(default = "default_door_angle")]
+        angle: f32,
+        // Local offset of the hinge from the node's origin
+        #[serde(default)]
+        hinge: Vec3,
+    },
+    Lever {
+        id: String,
+        // Ids of the doors and levers it toggles
+        targets: Vec<String>,
+        #[serde(default)]
+        on: bool,
+    },
+    Chest {
+        id: String,
+        #[serde(default)]
+        souls: u32,
+    },
+}
+
+impl BInteractable {
+    pub fn id(&self) -> &str {
+        match self {
+            BInteractable::Door { id, .. } | BInteractable::Lever { id, .. } | BInteractable::Chest { id, .. } => id,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), (String, String)> {
+        if self.id().is_empty() {
+            return Err(("interactable.id".to_string(), "interactables need an id".to_string()));
+        }
+        if let BInteractable::Lever { targets, .. } = self {
+            if targets.is_empty() {
+                return Err(("interactable.Lever.targets".to_string(), "levers need at least one target".to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_door_angle() -> f32 {
+    90.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BWater {
+    // Upward push at full submersion, in multiples of gravity. Above 1 floats, below 1 sinks
+    #[serde(default = "default_buoyancy")]
+    pub buoyancy: f32,
+    #[serde(default = "default_water_linear_drag")]
+    pub linear_drag: f32,
+    #[serde(default = "default_water_angular_drag")]
+    pub angular_drag: f32,
+}
+
+fn default_buoyancy() -> f32 {
+    1.5
+}
+
+fn default_water_linear_drag() -> f32 {
+    2.0
+}
+
+fn default_water_angular_drag() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum BPathMode {
+    // Back and forth along the waypoints
+    #[default]
+    PingPong,
+    // From the last waypoint straight back to the first
+    Loop,
+    // Elevators, travel to the other end only while the player stands on them
+    OnTrigger,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BPath {
+    // Offsets from the platform's start position, Waypoint child empties are used when empty
+    #[serde(default)]
+    pub waypoints: Vec<Vec3>,
+    #[serde(default)]
+    pub mode: BPathMode,
+    #[serde(default = "default_path_speed")]
+    pub speed: f32,
+    // Seconds to stop at every waypoint
+    #[serde(default)]
+    pub wait: f32,
+}
+
+fn default_path_speed() -> f32 {
+    2.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BJointKind {
+    // Doors, drawbridges
+    Revolute,
+    // Sliding doors, pistons
+    Prismatic,
+    Fixed,
+    // Chains and ropes
+    Distance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BJoint {
+    pub kind: BJointKind,
+    // Name of the node on the other end, usually a static frame or the previous chain link
+    pub target: String,
+    // Attachment points in the local space of this body and of the target
+    #[serde(default)]
+    pub anchor: Vec3,
+    #[serde(default)]
+    pub target_anchor: Vec3,
+    // Hinge axis for revolute joints, slide axis for prismatic joints
+    #[serde(default = "default_joint_axis")]
+    pub axis: Vec3,
+    // Degrees for revolute joints, meters for prismatic and distance joints
+    pub limits: Option<[f32; 2]>,
+    pub rest_length: Option<f32>,
+    pub compliance: Option<f32>,
+}
+
+fn default_joint_axis() -> Vec3 {
+    Vec3::Y
+}
+
+// Build and spawn the avian joint between two level bodies
+fn spawn_joint(commands: &mut Commands, scene: Entity, name: String, body: Entity, target: Entity, joint: &BJoint) {
+    let mut joint_commands = match joint.kind {
+        BJointKind::Revolute => {
+            let mut revolute = RevoluteJoint::new(target, body)
+                .with_aligned_axis(joint.axis)
+                .with_local_anchor_1(joint.target_anchor)
+                .with_local_anchor_2(joint.anchor);
+            if let Some([min, max]) = joint.limits {
+                revolute = revolute.with_angle_limits(min.to_radians(), max.to_radians());
+            }
+            if let Some(compliance) = joint.compliance {
+                revolute = revolute.with_compliance(compliance);
+            }
+            commands.spawn(revolute)
+        }
+        BJointKind::Prismatic => {
+            let mut prismatic = PrismaticJoint::new(target, body)
+                .with_free_axis(joint.axis)
+                .with_local_anchor_1(joint.target_anchor)
+                .with_local_anchor_2(joint.anchor);
+            if let Some([min, max]) = joint.limits {
+                prismatic = prismatic.with_limits(min, max);
+            }
+            if let Some(compliance) = joint.compliance {
+                prismatic = prismatic.with_compliance(compliance);
+            }
+            commands.spawn(prismatic)
+        }
+        BJointKind::Fixed => {
+            let mut fixed = FixedJoint::new(target, body)
+                .with_local_anchor_1(joint.target_anchor)
+                .with_local_anchor_2(joint.anchor);
+            if let Some(compliance) = joint.compliance {
+                fixed = fixed.with_compliance(compliance);
+            }
+            commands.spawn(fixed)
+        }
+        BJointKind::Distance => {
+            let mut distance = DistanceJoint::new(target, body)
+                .with_local_anchor_1(joint.target_anchor)
+                .with_local_anchor_2(joint.anchor)
+                .with_rest_length(joint.rest_length.unwrap_or(0.0));
+            if let Some([min, max]) = joint.limits {
+                distance = distance.with_limits(min, max);
+            }
+            if let Some(compliance) = joint.compliance {
+                distance = distance.with_compliance(compliance);
+            }
+            commands.spawn(distance)
+        }
+    };
+    // Parent the joint to the level so it goes away with it
+    joint_commands.insert(Name::new(name)).set_parent(scene);
+}
+
+// Named collision layers, anything that gets a collider should use one of the presets below
+#[derive(PhysicsLayer, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameLayer {
+    #[default]
+    Default,
+    Player,
+    Enemy,
+    Environment,
+    Projectile,
+    // Geometry the third person camera shouldn't go through
+    CameraBlocking,
+    Trigger,
+    // Friendly characters and the things they stand at, solid but not part of the level
+    // geometry the camera and navmesh use
+    Npc,
+}
+
+impl GameLayer {
+    pub fn player() -> CollisionLayers {
+        CollisionLayers::new(
+            GameLayer::Player,
+            [GameLayer::Default, GameLayer::Enemy, GameLayer::Environment, GameLayer::Projectile, GameLayer::Trigger, GameLayer::Npc],
+        )
+    }
+
+    pub fn enemy() -> CollisionLayers {
+        CollisionLayers::new(
+            GameLayer::Enemy,
+            [GameLayer::Default, GameLayer::Player, GameLayer::Enemy, GameLayer::Environment, GameLayer::Projectile, GameLayer::Trigger, GameLayer::Npc],
+        )
+    }
+
+    // Npcs and level-up stations
+    pub fn npc() -> CollisionLayers {
+        CollisionLayers::new(
+            GameLayer::Npc,
+            [GameLayer::Default, GameLayer::Player, GameLayer::Enemy, GameLayer::Environment, GameLayer::Projectile],
+        )
+    }
+
+    // Static level geometry
+    pub fn environment() -> CollisionLayers {
+        CollisionLayers::new(
+            [GameLayer::Environment, GameLayer::CameraBlocking],
+            [GameLayer::Default, GameLayer::Player, GameLayer::Enemy, GameLayer::Environment, GameLayer::Projectile],
+        )
+    }
+
+    // Loose physics props like the chrome spheres, they don't block the camera
+    pub fn prop() -> CollisionLayers {
+        CollisionLayers::new(
+            GameLayer::Environment,
+            [GameLayer::Default, GameLayer::Player, GameLayer::Enemy, GameLayer::Environment, GameLayer::Projectile],
+        )
+    }
+
+    pub fn projectile() -> CollisionLayers {
+        CollisionLayers::new(
+            GameLayer::Projectile,
+            [GameLayer::Player, GameLayer::Enemy, GameLayer::Environment, GameLayer::Npc],
+        )
+    }
+
+    pub fn trigger() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::Trigger, [GameLayer::Player, GameLayer::Enemy])
+    }
+
+    pub fn mask(layers: &[GameLayer]) -> LayerMask {
+        LayerMask(layers.iter().fold(0, |bits, layer| bits | layer.to_bits()))
+    }
+
+    // Preset with the memberships and/or filters replaced by the ones authored in a level
+    pub fn with_overrides(
+        preset: CollisionLayers,
+        layers: Option<&[GameLayer]>,
+        collides_with: Option<&[GameLayer]>,
+    ) -> CollisionLayers {
+        CollisionLayers::new(
+            layers.map_or(preset.memberships, GameLayer::mask),
+            collides_with.map_or(preset.filters, GameLayer::mask),
+        )
+    }
+}
+
+/// Extras for gameplay markers, read from the node extras of empties
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BNodeExtras {
+    pub marker: Option<BMarker>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BMarker {
+    PlayerStart,
+    NpcSpawn { id: String },
+    Station,
+    // The id is what its collected state is saved under, unique within a level
+    Pickup { id: String, souls: u32 },
+    EnemySpawner { enemy: String },
+    // Child empties of a moving platform node, visited in index order
+    Waypoint { index: u32 },
+    // Where portals from other levels put the player
+    Destination { id: String },
+}
+
+// Marker empty placed in the level, the player and npc plugins spawn things at these
+#[derive(Component, Debug)]
+pub struct LevelMarker(pub BMarker);
+
+// Sent once a level scene has been spawned and its extras applied
+#[derive(Event)]
+pub struct LevelSpawned {
+    pub scene: Entity,
+    // The scene was respawned because its file changed, see world::hot_reload_level
+    pub reloaded: bool,
+}
+
+// Put on a level scene root that replaces an older instance of the same file
+#[derive(Component)]
+pub struct ReloadedLevel;
+
+// Things spawned by gameplay code for a level scene (e.g. at its markers), they are
+// despawned together with that scene
+#[derive(Component)]
+pub struct SpawnedByLevel(pub Entity);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BTrigger {
+    KillZone,
+    AreaDiscovery { id: String, name: String },
+    Checkpoint { id: String },
+    CutsceneStart { id: String },
+    DamageOverTime { damage_per_second: f32 },
+    // Finishes the dungeon it belongs to, placed by the dungeon generator
+    DungeonExit,
+    // Takes the player to another level from the LevelRegistry, at the Destination marker with
+    // the given id or at the level's PlayerStart
+    Portal { level: String, destination: Option<String> },
+}
+
+impl From<&BRigidBody> for RigidBody {
+    fn from(rigid_body: &BRigidBody) -> Self {
+        match rigid_body {
+            BRigidBody::Static => RigidBody::Static,
+            BRigidBody::Dynamic => RigidBody::Dynamic,
+            BRigidBody::Kinematic => RigidBody::Kinematic,
+        }
+    }
+}
+
+impl BMeshExtras {
+    // Checks the things serde can't, so bad nodes are reported instead of panicking later
+    fn validate(&self) -> Result<(), (String, String)> {
+        if let BCollider::Cubiod = self.collider {
+            let Some(size) = self.cube_size else {
+                return Err(("cube_size".to_string(), "Cubiod collider must have cube_size".to_string()));
+            };
+            if size.min_element() <= 0.0 {
+                return Err(("cube_size".to_string(), format!("cube_size must be positive, got {size}")));
+            }
+        }
+        if matches!(self.collider, BCollider::Sphere | BCollider::Capsule | BCollider::Cylinder) {
+            if self.radius <= 0.0 {
+                return Err(("radius".to_string(), format!("radius must be positive, got {}", self.radius)));
+            }
+            if self.height < 0.0 {
+                return Err(("height".to_string(), format!("height can't be negative, got {}", self.height)));
+            }
+        }
+        if let Some(mass) = self.mass {
+            if mass <= 0.0 {
+                return Err(("mass".to_string(), format!("mass must be positive, got {mass}")));
+            }
+        }
+        if let Some(friction) = self.friction {
+            if friction < 0.0 {
+                return Err(("friction".to_string(), format!("friction can't be negative, got {friction}")));
+            }
+        }
+        if let Some(restitution) = self.restitution {
+            if restitution < 0.0 {
+                return Err(("restitution".to_string(), format!("restitution can't be negative, got {restitution}")));
+            }
+        }
+        if let Some(BTrigger::DamageOverTime { damage_per_second }) = self.trigger {
+            if damage_per_second <= 0.0 {
+                return Err((
+                    "trigger.DamageOverTime.damage_per_second".to_string(),
+                    format!("damage_per_second must be positive, got {damage_per_second}"),
+                ));
+            }
+        }
+        if let Some(BTrigger::Portal { level, .. }) = &self.trigger {
+            if level.is_empty() {
+                return Err(("trigger.Portal.level".to_string(), "portals need a level to lead to".to_string()));
+            }
+        }
+        if let Some(destructible) = &self.destructible {
+            if destructible.health <= 0.0 {
+                return Err(("destructible.health".to_string(), format!("health must be positive, got {}", destructible.health)));
+            }
+            if destructible.debris_lifetime < 0.0 {
+                return Err((
+                    "destructible.debris_lifetime".to_string(),
+                    format!("debris_lifetime can't be negative, got {}", destructible.debris_lifetime),
+                ));
+            }
+        }
+        if let Some(interactable) = &self.interactable {
+            interactable.validate()?;
+        }
+        if let Some(water) = &self.water {
+            if self.trigger.is_some() {
+                return Err(("water".to_string(), "a node can't be both water and a trigger".to_string()));
+            }
+            if water.buoyancy < 0.0 || water.linear_drag < 0.0 || water.angular_drag < 0.0 {
+                return Err(("water".to_string(), "buoyancy and drag can't be negative".to_string()));
+            }
+        }
+        if let Some(path) = &self.path {
+            if !matches!(self.rigid_body, BRigidBody::Kinematic) {
+                return Err(("rigid_body".to_string(), "nodes with a path must be Kinematic".to_string()));
+            }
+            if path.speed <= 0.0 {
+                return Err(("path.speed".to_string(), format!("path speed must be positive, got {}", path.speed)));
+            }
+            if path.wait < 0.0 {
+                return Err(("path.wait".to_string(), format!("path wait can't be negative, got {}", path.wait)));
+            }
+        }
+        if let Some(joint) = &self.joint {
+            if joint.target.is_empty() {
+                return Err(("joint.target".to_string(), "joints need a target node".to_string()));
+            }
+            if joint.axis.length_squared() == 0.0 {
+                return Err(("joint.axis".to_string(), "joint axis can't be zero".to_string()));
+            }
+            if let Some([min, max]) = joint.limits {
+                if min > max {
+                    return Err(("joint.limits".to_string(), format!("joint limits are reversed, got [{min}, {max}]")));
+                }
+            }
+            if let Some(rest_length) = joint.rest_length {
+                if rest_length < 0.0 {
+                    return Err(("joint.rest_length".to_string(), format!("rest_length can't be negative, got {rest_length}")));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A single node whose extras were skipped while loading a level
+#[derive(Debug, Clone)]
+pub struct LevelExtrasError {
+    pub node: String,
+    pub extras: String,
+    // Where in the extras the problem is, e.g. "collider" or "cube_size"
+    pub path: String,
+    pub message: String,
+}
+
+// Outcome of the extras -> physics pass, kept as a resource and sent as an event
+#[derive(Resource, Event, Debug, Clone, Default)]
+pub struct LevelLoadReport {
+    pub scene: Option<Entity>,
+    pub applied: usize,
+    pub markers: usize,
+    pub joints: usize,
+    pub errors: Vec<LevelExtrasError>,
+}
+
+impl LevelLoadReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    // Log a skipped node and keep it for the report
+    pub fn push_error(&mut self, err: LevelExtrasError) {
+        error!(
+            "Skipping level node '{}': {} (at '{}') in extras {}",
+            err.node, err.message, err.path, err.extras
+        );
+        self.errors.push(err);
+    }
+}
+
+fn parse_extras<T: DeserializeOwned>(node: &str, extras: &str) -> Result<T, LevelExtrasError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(extras);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| LevelExtrasError {
+        node: node.to_string(),
+        extras: extras.to_string(),
+        path: err.path().to_string(),
+        message: err.into_inner().to_string(),
+    })
+}
+
+// Parse and validate the extras of one node, without touching the world
+pub fn parse_mesh_extras(node: &str, extras: &str) -> Result<BMeshExtras, LevelExtrasError> {
+    let data: BMeshExtras = parse_extras(node, extras)?;
+    data.validate().map_err(|(path, message)| LevelExtrasError {
+        node: node.to_string(),
+        extras: extras.to_string(),
+        path,
+        message,
+    })?;
+    Ok(data)
+}
+
+pub fn parse_node_extras(node: &str, extras: &str) -> Result<BNodeExtras, LevelExtrasError> {
+    parse_extras(node, extras)
+}
+
+fn node_name(entity: Entity, names: &Query<&Name>) -> String {
+    names
+        .get(entity)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|_| format!("{entity}"))
+}
+
+// Mesh primitives are named after the mesh, the gltf node name artists see is on their parent
+fn mesh_node_name(entity: Entity, names: &Query<&Name>, parents: &Query<&Parent>) -> String {
+    parents
+        .get(entity)
+        .ok()
+        .and_then(|parent| names.get(parent.get()).ok())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| node_name(entity, names))
 }
 
 // System to add physics to the scene from gltf extras
@@ -42,55 +655,175 @@ pub fn on_level_spawn(
     mut commands: Commands,
     children: Query<&Children>,
     extras: Query<&GltfMeshExtras>,
+    node_extras: Query<&GltfExtras>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mut report_events: EventWriter<LevelLoadReport>,
+    mut spawned_events: EventWriter<LevelSpawned>,
+    reloaded_levels: Query<(), With<ReloadedLevel>>,
+    asset_server: Res<AssetServer>,
 ) {
+    let mut report = LevelLoadReport {
+        scene: Some(trigger.entity()),
+        ..default()
+    };
+    // Joints can point at nodes further down the hierarchy, so resolve them once every body is known
+    let mut bodies = HashMap::new();
+    let mut pending_joints = Vec::new();
     for entity in
         children.iter_descendants(trigger.entity())
     {
+        if let Ok(gltf_extras) = node_extras.get(entity) {
+            let node = node_name(entity, &names);
+            match parse_node_extras(&node, &gltf_extras.value) {
+                Ok(BNodeExtras { marker: Some(marker) }) => {
+                    commands.entity(entity).insert(LevelMarker(marker));
+                    report.markers += 1;
+                }
+                Ok(_) => {}
+                Err(err) => report.push_error(err),
+            }
+        }
+
         let Ok(gltf_mesh_extras) = extras.get(entity)
         else {
             continue;
         };
-        let Ok(data) = serde_json::from_str::<BMeshExtras>(
-            &gltf_mesh_extras.value,
-        ) else {
-            error!("couldn't deseralize extras");
-            continue;
+        let node = mesh_node_name(entity, &names, &parents);
+        let data = match parse_mesh_extras(&node, &gltf_mesh_extras.value) {
+            Ok(data) => data,
+            Err(err) => {
+                report.push_error(err);
+                continue;
+            }
         };
-        dbg!(&data);
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(RigidBody::from(&data.rigid_body));
         match data.collider {
             BCollider::TrimeshFromMesh => {
-                commands.entity(entity).insert((
-                    match data.rigid_body {
-                        BRigidBody::Static => {
-                            RigidBody::Static
-                        }
-                        BRigidBody::Dynamic => {
-                            RigidBody::Dynamic
-                        }
-                    },
-                    ColliderConstructor::TrimeshFromMesh,
-                ));
+                entity_commands.insert(ColliderConstructor::TrimeshFromMesh);
+            }
+            BCollider::ConvexHullFromMesh => {
+                entity_commands.insert(ColliderConstructor::ConvexHullFromMesh);
             }
             BCollider::Cubiod => {
-                let size = data.cube_size.expect(
-                    "Cubiod collider must have cube_size",
-                );
-                commands.entity(entity).insert((
-                    match data.rigid_body {
-                        BRigidBody::Static => {
-                            RigidBody::Static
-                        }
-                        BRigidBody::Dynamic => {
-                            RigidBody::Dynamic
-                        }
-                    },
-                    Collider::cuboid(
-                        size.x, size.y, size.z,
-                    ),
+                // Checked by validate()
+                let size = data.cube_size.unwrap_or(Vec3::ONE);
+                entity_commands.insert(Collider::cuboid(
+                    size.x, size.y, size.z,
                 ));
             }
+            BCollider::Sphere => {
+                entity_commands.insert(Collider::sphere(data.radius));
+            }
+            BCollider::Capsule => {
+                entity_commands.insert(Collider::capsule(data.radius, data.height));
+            }
+            BCollider::Cylinder => {
+                entity_commands.insert(Collider::cylinder(data.radius, data.height));
+            }
+        }
+        if let Some(mass) = data.mass {
+            entity_commands.insert(Mass(mass));
+        }
+        if let Some(friction) = data.friction {
+            entity_commands.insert(Friction::new(friction));
+        }
+        if let Some(restitution) = data.restitution {
+            entity_commands.insert(Restitution::new(restitution));
+        }
+        let default_layers = if data.trigger.is_some() || data.water.is_some() {
+            GameLayer::trigger()
+        } else {
+            GameLayer::environment()
+        };
+        entity_commands.insert(GameLayer::with_overrides(
+            default_layers,
+            data.layers.as_deref(),
+            data.collides_with.as_deref(),
+        ));
+        if let Some(kind) = data.trigger {
+            entity_commands.insert((
+                Sensor,
+                TriggerVolume {
+                    kind,
+                    player_inside: false,
+                },
+            ));
+        }
+        if let Some(destructible) = data.destructible {
+            entity_commands.insert(Destructible {
+                health: destructible.health,
+                max_health: destructible.health,
+                fracture_scene: destructible
+                    .fracture_scene
+                    .map(|path| asset_server.load(path)),
+                debris_lifetime: destructible.debris_lifetime,
+            });
+        }
+        if let Some(water) = data.water {
+            entity_commands.insert((
+                Sensor,
+                WaterVolume {
+                    buoyancy: water.buoyancy,
+                    linear_drag: water.linear_drag,
+                    angular_drag: water.angular_drag,
+                },
+            ));
+        }
+        if let Some(surface) = data.scatter_surface {
+            entity_commands.insert(ScatterSurface(surface));
         }
+        if let Some(interactable) = &data.interactable {
+            insert_interactable(&mut entity_commands, interactable);
+        }
+        if let Some(path) = data.path {
+            // Waypoint empties are siblings of the mesh, under the node that doesn't move
+            let node_entity = parents.get(entity).map_or(entity, |parent| parent.get());
+            entity_commands.insert(MovingPlatform::new(path, node_entity));
+        }
+        if let Some(joint) = data.joint {
+            pending_joints.push((entity, node.clone(), joint, gltf_mesh_extras.value.clone()));
+        }
+        bodies.insert(node, entity);
+        report.applied += 1;
+    }
+
+    for (entity, node, joint, extras) in pending_joints {
+        let Some(&target) = bodies.get(&joint.target) else {
+            report.push_error(LevelExtrasError {
+                message: format!("joint target '{}' is not a physics node in this level", joint.target),
+                node,
+                extras,
+                path: "joint.target".to_string(),
+            });
+            continue;
+        };
+        let name = format!("Joint {} -> {}", node, joint.target);
+        spawn_joint(&mut commands, trigger.entity(), name, entity, target, &joint);
+        report.joints += 1;
     }
+
+    if report.is_ok() {
+        info!(
+            "Level physics applied to {} nodes, {} markers, {} joints",
+            report.applied, report.markers, report.joints
+        );
+    } else {
+        warn!(
+            "Level physics applied to {} nodes, {} markers, {} joints, skipped {} with bad extras",
+            report.applied,
+            report.markers,
+            report.joints,
+            report.errors.len()
+        );
+    }
+    commands.insert_resource(report.clone());
+    report_events.send(report);
+    spawned_events.send(LevelSpawned {
+        scene: trigger.entity(),
+        reloaded: reloaded_levels.contains(trigger.entity()),
+    });
 }
 
 pub struct AvPhysicsPlugin;
@@ -104,7 +837,143 @@ impl Plugin for AvPhysicsPlugin {
             TnuaAvian3dPlugin::new(Update),
             TnuaControllerPlugin::new(Update),
         ))
-        .insert_resource(Gravity(Vec3::NEG_Y * 19.6));
+        .insert_resource(Gravity(Vec3::NEG_Y * 19.6))
+        .init_resource::<LevelLoadReport>()
+        .add_event::<LevelLoadReport>()
+        .add_event::<LevelSpawned>();
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuboid_without_cube_size_is_rejected() {
+        let err = parse_mesh_extras("Crate", r#"{"collider": "Cubiod"}"#).unwrap_err();
+        assert_eq!(err.node, "Crate");
+        assert_eq!(err.path, "cube_size");
+    }
+
+    #[test]
+    fn non_positive_cube_size_is_rejected() {
+        for size in ["[1.0, 0.0, 1.0]", "[1.0, 1.0, -2.0]"] {
+            let extras = format!(r#"{{"collider": "Cuboid", "cube_size": {size}}}"#);
+            let err = parse_mesh_extras("Crate", &extras).unwrap_err();
+            assert_eq!(err.path, "cube_size");
+            assert!(err.message.contains("must be positive"), "{}", err.message);
+        }
+        assert!(parse_mesh_extras("Crate", r#"{"collider": "Cuboid", "cube_size": [1.0, 2.0, 3.0]}"#).is_ok());
+    }
+
+    #[test]
+    fn unknown_variant_reports_its_path() {
+        let err = parse_mesh_extras("Wall", r#"{"collider": "Cube"}"#).unwrap_err();
+        assert_eq!(err.path, "collider");
+        assert!(err.message.contains("unknown variant `Cube`"), "{}", err.message);
+
+        let err = parse_mesh_extras("Pit", r#"{"trigger": {"Portal": {"levl": "crypt"}}}"#).unwrap_err();
+        assert!(err.path.starts_with("trigger"), "{}", err.path);
+        assert!(err.message.contains("missing field `level`"), "{}", err.message);
+
+        let err = parse_node_extras("Spawn", r#"{"marker": "PlayerEnd"}"#).unwrap_err();
+        assert_eq!(err.path, "marker");
+    }
+
+    #[test]
+    fn joint_without_target_is_rejected() {
+        let err = parse_mesh_extras("Hinge", r#"{"joint": {"kind": "Revolute"}}"#).unwrap_err();
+        assert!(err.path.starts_with("joint"), "{}", err.path);
+        assert!(err.message.contains("missing field `target`"), "{}", err.message);
+
+        let err = parse_mesh_extras("Hinge", r#"{"joint": {"kind": "Revolute", "target": ""}}"#).unwrap_err();
+        assert_eq!(err.path, "joint.target");
+        assert!(parse_mesh_extras("Hinge", r#"{"joint": {"kind": "Revolute", "target": "Frame"}}"#).is_ok());
+    }
+
+    #[test]
+    fn water_with_negative_buoyancy_is_rejected() {
+        let err = parse_mesh_extras("Lake", r#"{"water": {"buoyancy": -1.0}}"#).unwrap_err();
+        assert_eq!(err.path, "water");
+        assert!(err.message.contains("can't be negative"), "{}", err.message);
+
+        let err = parse_mesh_extras("Lake", r#"{"water": {"linear_drag": -0.5}}"#).unwrap_err();
+        assert_eq!(err.path, "water");
+        assert!(parse_mesh_extras("Lake", r#"{"water": {}}"#).is_ok());
+    }
+
+    #[test]
+    fn destructible_with_zero_health_is_rejected() {
+        let err = parse_mesh_extras("Crate", r#"{"destructible": {"health": 0.0}}"#).unwrap_err();
+        assert_eq!(err.path, "destructible.health");
+        assert!(err.message.contains("must be positive"), "{}", err.message);
+        assert!(parse_mesh_extras("Crate", r#"{"destructible": {}}"#).is_ok());
+    }
+
+    #[test]
+    fn unknown_layer_name_is_rejected() {
+        let err = parse_mesh_extras("Wall", r#"{"layers": ["Environment", "Enemies"]}"#).unwrap_err();
+        assert_eq!(err.path, "layers[1]");
+        assert!(err.message.contains("unknown variant `Enemies`"), "{}", err.message);
+
+        let err = parse_mesh_extras("Wall", r#"{"collides_with": ["Plyer"]}"#).unwrap_err();
+        assert_eq!(err.path, "collides_with[0]");
+        assert!(parse_mesh_extras("Wall", r#"{"layers": ["Environment", "CameraBlocking"]}"#).is_ok());
+    }
+
+    #[test]
+    fn every_validation_failure_reports_its_path() {
+        let cases = [
+            (r#"{"collider": "Sphere", "radius": 0.0}"#, "radius"),
+            (r#"{"collider": "Capsule", "height": -1.0}"#, "height"),
+            (r#"{"mass": 0.0}"#, "mass"),
+            (r#"{"friction": -0.1}"#, "friction"),
+            (r#"{"restitution": -0.1}"#, "restitution"),
+            (r#"{"trigger": {"DamageOverTime": {"damage_per_second": 0.0}}}"#, "trigger.DamageOverTime.damage_per_second"),
+            (r#"{"trigger": {"Portal": {"level": ""}}}"#, "trigger.Portal.level"),
+            (r#"{"destructible": {"debris_lifetime": -1.0}}"#, "destructible.debris_lifetime"),
+            (r#"{"interactable": {"Door": {"id": ""}}}"#, "interactable.id"),
+            (r#"{"interactable": {"Lever": {"id": "lever", "targets": []}}}"#, "interactable.Lever.targets"),
+            (r#"{"water": {}, "trigger": "KillZone"}"#, "water"),
+            (r#"{"path": {"speed": 1.0}}"#, "rigid_body"),
+            (r#"{"rigid_body": "Kinematic", "path": {"speed": 0.0}}"#, "path.speed"),
+            (r#"{"rigid_body": "Kinematic", "path": {"wait": -1.0}}"#, "path.wait"),
+            (r#"{"joint": {"kind": "Prismatic", "target": "Frame", "axis": [0.0, 0.0, 0.0]}}"#, "joint.axis"),
+            (r#"{"joint": {"kind": "Revolute", "target": "Frame", "limits": [90.0, -90.0]}}"#, "joint.limits"),
+            (r#"{"joint": {"kind": "Distance", "target": "Link", "rest_length": -1.0}}"#, "joint.rest_length"),
+        ];
+        for (extras, path) in cases {
+            let err = parse_mesh_extras("Node", extras).unwrap_err();
+            assert_eq!(err.path, path, "{}", extras);
+        }
+    }
+
+    #[test]
+    fn report_keeps_every_skipped_node() {
+        let mut report = LevelLoadReport::default();
+        let nodes = [
+            ("Floor", r#"{"collider": "TrimeshFromMesh"}"#),
+            ("Crate", r#"{"collider": "Cubiod", "cube_size": [0.0, 1.0, 1.0]}"#),
+            ("Ball", r#"{"collider": "Sphere", "radius": -1.0}"#),
+            ("Broken", r#"{"collider": "#),
+        ];
+        for (node, extras) in nodes {
+            match parse_mesh_extras(node, extras) {
+                Ok(_) => report.applied += 1,
+                Err(err) => report.push_error(err),
+            }
+        }
+
+        assert!(!report.is_ok());
+        assert_eq!(report.applied, 1);
+        let skipped: Vec<(&str, &str)> = report
+            .errors
+            .iter()
+            .map(|err| (err.node.as_str(), err.path.as_str()))
+            .collect();
+        assert_eq!(skipped[0], ("Crate", "cube_size"));
+        assert_eq!(skipped[1], ("Ball", "radius"));
+        assert_eq!(skipped[2].0, "Broken");
+        assert_eq!(report.errors[1].extras, r#"{"collider": "Sphere", "radius": -1.0}"#);
+    }
+}
@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::npcs::spawn_feedback_text;
+use crate::physics::{BMarker, LevelMarker};
+use crate::player::Player;
+use crate::transitions::LevelTransition;
+use crate::ui::GameUI;
+use crate::world::{LevelEntity, LevelSelection};
+
+// How close the player has to walk to a pickup to absorb it
+const PICKUP_RADIUS: f32 = 1.2;
+// Height the pickup floats at over its marker, and how far it bobs up and down
+const HOVER_HEIGHT: f32 = 1.0;
+const BOB_HEIGHT: f32 = 0.15;
+const BOB_SPEED: f32 = 2.0;
+const GLOW_INTENSITY: f32 = 3000.0;
+// Seconds the pickup takes to fly into the player
+const ABSORB_TIME: f32 = 0.35;
+const SOUL_COLOR: Color = Color::srgb(0.7, 0.85, 1.0);
+
+pub struct PickupsPlugin;
+
+impl Plugin for PickupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollectedPickups>()
+            .add_event::<SoulDrop>()
+            .add_systems(Startup, setup_pickup_assets)
+            .add_systems(Update, (
+                spawn_marker_pickups,
+                spawn_dropped_souls,
+                animate_pickups,
+                absorb_pickups,
+            ).chain());
+    }
+}
+
+// Floating souls the player absorbs by walking through them
+#[derive(Component)]
+pub struct SoulPickup {
+    // Set for pickups placed by the level, dropped souls don't stay collected
+    pub id: Option<String>,
+    pub souls: u32,
+    // Offsets the bobbing so pickups next to each other don't move in sync
+    phase: f32,
+    // Where the pickup hovers, in its parent's space
+    origin: Vec3,
+    // Time since the player touched it
+    absorbing: Option<f32>,
+}
+
+impl SoulPickup {
+    fn new(id: Option<String>, souls: u32, origin: Vec3) -> Self {
+        Self {
+            id,
+            souls,
+            phase: origin.x * 1.3 + origin.z * 0.7,
+            origin,
+            absorbing: None,
+        }
+    }
+}
+
+// Souls left in the world, e.g. where an enemy died. Spawns a pickup at the position.
+#[derive(Event)]
+pub struct SoulDrop {
+    pub position: Vec3,
+    pub souls: u32,
+}
+
+// Level pickups already absorbed by level and id, so they don't come back when the level loads
+// again. Serializable like InteractableStates so it can go into a save file.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CollectedPickups {
+    pub levels: HashMap<String, HashSet<String>>,
+}
+
+impl CollectedPickups {
+    pub fn contains(&self, level: &str, id: &str) -> bool {
+        self.levels.get(level).is_some_and(|ids| ids.contains(id))
+    }
+
+    pub fn insert(&mut self, level: &str, id: &str) {
+        self.levels.entry(level.to_string()).or_default().insert(id.to_string());
+    }
+}
+
+#[derive(Resource)]
+struct PickupAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup_pickup_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(PickupAssets {
+        mesh: meshes.add(Sphere::new(0.2)),
+        material: materials.add(StandardMaterial {
+            base_color: SOUL_COLOR,
+            emissive: LinearRgba::from(SOUL_COLOR) * 8.0,
+            ..default()
+        }),
+    });
+}
+
+fn pickup_bundle(assets: &PickupAssets, id: Option<String>, souls: u32, origin: Vec3) -> impl Bundle {
+    (
+        Name::new(format!("Souls ({})", souls)),
+        Mesh3d(assets.mesh.clone()),
+        MeshMaterial3d(assets.material.clone()),
+        PointLight {
+            color: SOUL_COLOR,
+            intensity: GLOW_INTENSITY,
+            range: 4.0,
+            shadows_enabled: false,
+            ..default()
+        },
+        Transform::from_translation(origin),
+        SoulPickup::new(id, souls, origin),
+    )
+}
+
+// Pickup markers get their pickup as a child, so it goes away with the level
+fn spawn_marker_pickups(
+    mut commands: Commands,
+    assets: Res<PickupAssets>,
+    collected: Res<CollectedPickups>,
+    level: Res<LevelSelection>,
+    markers: Query<(Entity, &LevelMarker), Added<LevelMarker>>,
+) {
+    for (entity, marker) in &markers {
+        let BMarker::Pickup { id, souls } = &marker.0 else {
+            continue;
+        };
+        if collected.contains(&level.name, id) {
+            continue;
+        }
+        let pickup = commands.spawn(pickup_bundle(&assets, Some(id.clone()), *souls, Vec3::Y * HOVER_HEIGHT)).id();
+        commands.entity(entity).add_child(pickup);
+    }
+}
+
+fn spawn_dropped_souls(
+    mut commands: Commands,
+    assets: Res<PickupAssets>,
+    mut drop_events: EventReader<SoulDrop>,
+) {
+    for event in drop_events.read() {
+        if event.souls == 0 {
+            continue;
+        }
+        commands.spawn((
+            pickup_bundle(&assets, None, event.souls, event.position + Vec3::Y * HOVER_HEIGHT),
+            LevelEntity,
+        ));
+    }
+}
+
+// Bob up and down with a pulsing glow
+fn animate_pickups(
+    time: Res<Time>,
+    mut pickups: Query<(&SoulPickup, &mut Transform, &mut PointLight)>,
+) {
+    let elapsed = time.elapsed_secs();
+    for (pickup, mut transform, mut light) in &mut pickups {
+        if pickup.absorbing.is_some() {
+            continue;
+        }
+        let wave = (elapsed * BOB_SPEED + pickup.phase).sin();
+        transform.translation = pickup.origin + Vec3::Y * wave * BOB_HEIGHT;
+        transform.rotate_y(time.delta_secs());
+        light.intensity = GLOW_INTENSITY * (0.75 + 0.25 * wave);
+    }
+}
+
+fn absorb_pickups(
+    mut commands: Commands,
+    time: Res<Time>,
+    transition: Res<LevelTransition>,
+    level: Res<LevelSelection>,
+    mut collected: ResMut<CollectedPickups>,
+    mut game_ui: ResMut<GameUI>,
+    mut pickups: Query<(Entity, &mut SoulPickup, &mut Transform, &GlobalTransform), Without<Player>>,
+    player_query: Query<(&Transform, &Player)>,
+) {
+    let Ok((player, player_state)) = player_query.get_single() else {
+        return;
+    };
+    // Souls on the ground stay there while the player lies dead next to them
+    let is_dead = player_state.is_dead;
+    // Aim for the chest rather than the feet
+    let target = player.translation + Vec3::Y * 0.5;
+
+    for (entity, mut pickup, mut transform, global_transform) in &mut pickups {
+        let Some(elapsed) = pickup.absorbing else {
+            if !transition.is_active() && !is_dead && global_transform.translation().distance(target) <= PICKUP_RADIUS {
+                // Detach from the marker so it can fly to the player in world space
+                pickup.absorbing = Some(0.0);
+                commands.entity(entity).remove_parent_in_place();
+            }
+            continue;
+        };
+
+        let elapsed = elapsed + time.delta_secs();
+        pickup.absorbing = Some(elapsed);
+        let t = (elapsed / ABSORB_TIME).min(1.0);
+        transform.translation = transform.translation.lerp(target, t);
+        transform.scale = Vec3::splat(1.0 - t);
+        if t < 1.0 {
+            continue;
+        }
+
+        game_ui.souls += pickup.souls as usize;
+        if let Some(id) = &pickup.id {
+            collected.insert(&level.name, id);
+        }
+        info!("Absorbed {} souls. Total: {}", pickup.souls, game_ui.souls);
+        spawn_feedback_text(&mut commands, format!("+ {} souls", pickup.souls), 200.0, 1.5);
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,175 @@
+use avian3d::prelude::{LinearVelocity, Position};
+use bevy::prelude::*;
+use bevy_tnua::prelude::*;
+
+use crate::physics::{BMarker, BPath, BPathMode, LevelMarker};
+use crate::player::Player;
+
+pub struct PlatformsPlugin;
+
+impl Plugin for PlatformsPlugin {
+    fn build(&self, app: &mut App) {
+        // Platforms are steered through their velocity, so they move in step with physics
+        app.add_systems(Update, init_platform_paths)
+            .add_systems(FixedUpdate, move_platforms);
+    }
+}
+
+// Kinematic body following a path authored in the level. It is moved through its velocity
+// rather than its transform so tnua carries the player standing on it.
+#[derive(Component)]
+pub struct MovingPlatform {
+    pub path: BPath,
+    // Gltf node the platform mesh belongs to, its Waypoint children describe the path
+    pub node: Entity,
+    // World space positions, the first one is where the platform started
+    waypoints: Vec<Vec3>,
+    target: usize,
+    forward: bool,
+    wait_timer: f32,
+    initialized: bool,
+    // OnTrigger platforms sit at an end of their path until the player steps on
+    parked: bool,
+    had_rider: bool,
+}
+
+impl MovingPlatform {
+    pub fn new(path: BPath, node: Entity) -> Self {
+        let parked = path.mode == BPathMode::OnTrigger;
+        Self {
+            path,
+            node,
+            waypoints: Vec::new(),
+            target: 1,
+            forward: true,
+            wait_timer: 0.0,
+            initialized: false,
+            parked,
+            had_rider: false,
+        }
+    }
+
+    // Pick the waypoint after the one we just reached
+    fn advance(&mut self) {
+        let last = self.waypoints.len() - 1;
+        match self.path.mode {
+            BPathMode::Loop => {
+                self.target = (self.target + 1) % self.waypoints.len();
+            }
+            BPathMode::PingPong | BPathMode::OnTrigger => {
+                if self.forward && self.target == last {
+                    self.forward = false;
+                } else if !self.forward && self.target == 0 {
+                    self.forward = true;
+                }
+                self.target = if self.forward { self.target + 1 } else { self.target - 1 };
+            }
+        }
+    }
+
+    // Elevators only stop at the two ends of their path
+    fn at_end(&self) -> bool {
+        self.target == 0 || self.target == self.waypoints.len() - 1
+    }
+}
+
+// Waypoint transforms are only valid once they have been propagated, so gather them a frame
+// after the level spawned. The start comes from the GlobalTransform too, Position is only
+// written by the first physics step.
+fn init_platform_paths(
+    mut platforms: Query<(&mut MovingPlatform, &GlobalTransform)>,
+    children: Query<&Children>,
+    waypoints: Query<(&LevelMarker, &GlobalTransform)>,
+) {
+    for (mut platform, transform) in &mut platforms {
+        if platform.initialized {
+            continue;
+        }
+        let start = transform.translation();
+        let mut path = vec![start];
+        if platform.path.waypoints.is_empty() {
+            let mut child_waypoints: Vec<(u32, Vec3)> = children
+                .get(platform.node)
+                .iter()
+                .flat_map(|children| children.iter())
+                .filter_map(|child| match waypoints.get(*child) {
+                    Ok((LevelMarker(BMarker::Waypoint { index }), transform)) => {
+                        Some((*index, transform.translation()))
+                    }
+                    _ => None,
+                })
+                .collect();
+            child_waypoints.sort_by_key(|(index, _)| *index);
+            path.extend(child_waypoints.into_iter().map(|(_, point)| point));
+        } else {
+            path.extend(platform.path.waypoints.iter().map(|offset| start + *offset));
+        }
+
+        if path.len() < 2 {
+            warn!("Moving platform {} has no waypoints, it will stay put", platform.node);
+        }
+        platform.waypoints = path;
+        platform.initialized = true;
+    }
+}
+
+fn move_platforms(
+    mut platforms: Query<(Entity, &mut MovingPlatform, &Position, &mut LinearVelocity)>,
+    player_query: Query<&TnuaController, With<Player>>,
+    time: Res<Time<Fixed>>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+
+    // Entity the player is currently standing on, used to call elevators
+    let standing_on = player_query
+        .get_single()
+        .ok()
+        .and_then(|controller| controller.concrete_basis::<TnuaBuiltinWalk>())
+        .and_then(|(_, basis_state)| basis_state.standing_on_entity());
+
+    for (entity, mut platform, position, mut velocity) in &mut platforms {
+        if !platform.initialized || platform.waypoints.len() < 2 {
+            velocity.0 = Vec3::ZERO;
+            continue;
+        }
+
+        let rider = standing_on == Some(entity);
+        if platform.parked {
+            // Only leave when the player steps on, not while they're still on from the last ride
+            let stepped_on = rider && !platform.had_rider;
+            platform.had_rider = rider;
+            velocity.0 = Vec3::ZERO;
+            if !stepped_on {
+                continue;
+            }
+            platform.parked = false;
+        }
+
+        if platform.wait_timer > 0.0 {
+            platform.wait_timer -= dt;
+            velocity.0 = Vec3::ZERO;
+            continue;
+        }
+
+        let target = platform.waypoints[platform.target];
+        let to_target = target - position.0;
+        let distance = to_target.length();
+        let step = platform.path.speed * dt;
+
+        if distance <= step {
+            // Land exactly on the waypoint this step, then move on to the next one
+            velocity.0 = to_target / dt;
+            if platform.path.mode == BPathMode::OnTrigger && platform.at_end() {
+                platform.parked = true;
+                platform.had_rider = rider;
+            }
+            platform.advance();
+            platform.wait_timer = platform.path.wait;
+        } else {
+            velocity.0 = to_target / distance * platform.path.speed;
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use avian3d::prelude::{Collider, LockedAxes, RigidBody};
+use avian3d::prelude::{Collider, LinearVelocity, LockedAxes, RigidBody};
 use bevy::{
-    input::keyboard::KeyCode, prelude::*
+    input::keyboard::KeyCode, prelude::*, transform::helper::TransformHelper
 };
-use bevy_tnua::{prelude::TnuaController, TnuaAnimatingState};
+use bevy_tnua::{prelude::{TnuaBuiltinWalk, TnuaController}, TnuaAnimatingState};
 use bevy_tnua_avian3d::TnuaAvian3dSensorShape;
 use crate::camera::ThirdPersonCamera;
+use crate::physics::{BMarker, GameLayer, LevelMarker, ReloadedLevel};
+use crate::progression::PlayerProgress;
+use crate::transitions::LevelTransition;
 use crate::animation::{
     PlayerAnimationState, 
     RootMotionAnimation, 
@@ -18,6 +21,8 @@ const CHARACTER_PATH: &str = "models/character.glb";
 pub struct Player {
     pub is_moving: bool,
     pub is_attacking: bool,    // Flag for attack animation state
+    pub is_swimming: bool,     // Set by water.rs while the chest is under water
+    pub is_dead: bool,         // Set when health hits zero, cleared on respawn
     
     // Added for UI
     pub health: f32,
@@ -35,6 +40,8 @@ impl Default for Player {
         Self {
             is_moving: false,
             is_attacking: false,
+            is_swimming: false,
+            is_dead: false,
             
             // Stats for UI
             health: 100.0,
@@ -49,6 +56,125 @@ impl Default for Player {
     }
 }
 
+// Landing impact tuning, speeds are the vertical velocity when touching the ground.
+// With our gravity of 19.6 a 3.7m drop lands at ~12 m/s and a 20m drop at ~28 m/s.
+#[derive(Component)]
+pub struct FallDamage {
+    pub safe_speed: f32,       // Landing slower than this never hurts
+    pub lethal_speed: f32,     // Landing faster than this always kills
+    pub damage_per_speed: f32, // Damage per m/s above the safe speed
+    peak_fall_speed: f32,
+    airborne: bool,
+}
+
+impl Default for FallDamage {
+    fn default() -> Self {
+        Self {
+            safe_speed: 12.0,
+            lethal_speed: 28.0,
+            damage_per_speed: 6.25,
+            peak_fall_speed: 0.0,
+            airborne: false,
+        }
+    }
+}
+
+impl FallDamage {
+    // Forget the fall in progress, for when the player is moved somewhere else
+    pub fn reset(&mut self) {
+        self.peak_fall_speed = 0.0;
+        self.airborne = false;
+    }
+}
+
+// Each dexterity point shaves 2% off fall damage, up to half of it
+const FALL_DAMAGE_REDUCTION_PER_DEX: f32 = 0.02;
+const MAX_FALL_DAMAGE_REDUCTION: f32 = 0.5;
+
+#[derive(Event)]
+pub struct PlayerLanded {
+    pub impact_speed: f32,
+    pub damage: f32,
+}
+
+// Sent once when the player's health hits zero, controls stay locked until they respawn
+#[derive(Event)]
+pub struct PlayerDied {
+    pub position: Vec3,
+}
+
+// Damage after dexterity, or None for a lethal landing
+pub fn fall_damage(settings: &FallDamage, impact_speed: f32, dexterity: u32) -> Option<f32> {
+    if impact_speed >= settings.lethal_speed {
+        return None;
+    }
+    let raw_damage = (impact_speed - settings.safe_speed).max(0.0) * settings.damage_per_speed;
+    let reduction = (dexterity as f32 * FALL_DAMAGE_REDUCTION_PER_DEX).min(MAX_FALL_DAMAGE_REDUCTION);
+    Some(raw_damage * (1.0 - reduction))
+}
+
+// Track how fast the player falls and hurt them when they hit the ground
+fn apply_fall_damage(
+    mut player_query: Query<(&TnuaController, &LinearVelocity, &mut Player, &mut FallDamage)>,
+    player_progress: Res<PlayerProgress>,
+    mut landed_events: EventWriter<PlayerLanded>,
+) {
+    let Ok((controller, velocity, mut player, mut fall)) = player_query.get_single_mut() else {
+        return;
+    };
+    let Some((_, basis_state)) = controller.concrete_basis::<TnuaBuiltinWalk>() else {
+        return;
+    };
+    let grounded = basis_state.standing_on_entity().is_some();
+
+    if !grounded {
+        fall.airborne = true;
+        fall.peak_fall_speed = fall.peak_fall_speed.max(-velocity.y);
+        return;
+    }
+    if !fall.airborne {
+        return;
+    }
+
+    let impact_speed = fall.peak_fall_speed;
+    fall.airborne = false;
+    fall.peak_fall_speed = 0.0;
+
+    // Water breaks the fall
+    if player.is_swimming {
+        return;
+    }
+
+    let damage = match fall_damage(&fall, impact_speed, player_progress.dexterity) {
+        Some(damage) => damage,
+        None => {
+            info!("Lethal landing at {:.1} m/s", impact_speed);
+            player.health
+        }
+    };
+    if damage > 0.0 {
+        player.health = (player.health - damage).max(0.0);
+        info!("Landed at {:.1} m/s, took {:.1} fall damage", impact_speed, damage);
+    }
+    landed_events.send(PlayerLanded { impact_speed, damage });
+}
+
+fn detect_player_death(
+    mut player_query: Query<(&mut Player, &Transform)>,
+    mut died_events: EventWriter<PlayerDied>,
+) {
+    let Ok((mut player, transform)) = player_query.get_single_mut() else {
+        return;
+    };
+    if player.health <= 0.0 && !player.is_dead {
+        player.is_dead = true;
+        player.is_moving = false;
+        player.is_attacking = false;
+        info!("Player died at {}", transform.translation);
+        died_events.send(PlayerDied { position: transform.translation });
+    }
+}
+
 // Player movement system
 fn player_controller(
     _keyboard: Res<ButtonInput<KeyCode>>,
@@ -72,8 +198,9 @@ fn update_player_stats(
     time: Res<Time>,
 ) {
     for mut player in &mut player_query {
-        // Very slow natural health regeneration - only when not exhausted
-        if !player.exhausted && player.health < player.max_health {
+        // Very slow natural health regeneration - only when not exhausted.
+        // Dead players stay dead until they respawn
+        if !player.exhausted && player.health > 0.0 && player.health < player.max_health {
             player.health = (player.health + 0.5 * time.delta_secs()).min(player.max_health);
         }
     }
@@ -97,7 +224,10 @@ fn setup_player(
         TnuaController::default(),
         TnuaAvian3dSensorShape(Collider::cylinder(0.49, 0.0)),
         LockedAxes::ROTATION_LOCKED.unlock_rotation_y(),
+        // Also read by tnua to filter what the ground sensor can stand on
+        GameLayer::player(),
         Player::default(),
+        FallDamage::default(),
         RootMotionAnimation {
             enabled: true,
             previous_root_transform: None,
@@ -105,12 +235,52 @@ fn setup_player(
         },
         AnimationStateMachine::new(), // Add our state machine
         AnimationCancellation::default(), // Add cancellation component
-        Transform::from_xyz(0.0, 0.0, 0.0), // Initial position slightly above ground
+        Transform::from_xyz(0.0, 0.0, 0.0), // Moved to the level's PlayerStart marker once it loads
     )).with_children(|children|{
-        children.spawn((Collider::capsule(0.3, 1.0), Transform::from_xyz(0.0, 0.7, 0.0)));
+        children.spawn((Collider::capsule(0.3, 1.0), GameLayer::player(), Transform::from_xyz(0.0, 0.7, 0.0)));
     });
 }
 
+// Move the player to the PlayerStart marker of a freshly spawned level, if it has one
+fn place_player_at_start(
+    markers: Query<(Entity, &LevelMarker), Added<LevelMarker>>,
+    parents: Query<&Parent>,
+    reloaded_levels: Query<(), With<ReloadedLevel>>,
+    mut player_placement: ParamSet<(
+        TransformHelper,
+        Query<(&mut Transform, &mut LinearVelocity, &mut FallDamage), With<Player>>,
+    )>,
+    transition: Res<LevelTransition>,
+) {
+    // Levels entered through a portal place the player at its destination instead
+    if transition.is_active() {
+        return;
+    }
+    // Markers come from glb scenes and from the level manifest alike. A hot reloaded level
+    // keeps the player where they stood.
+    let Some(start) = markers.iter().find(|(entity, marker)| {
+        marker.0 == BMarker::PlayerStart
+            && !parents.iter_ancestors(*entity).any(|ancestor| reloaded_levels.contains(ancestor))
+    }) else {
+        return;
+    };
+    // The marker may have been spawned this frame, before its GlobalTransform was propagated
+    let Ok(start) = player_placement.p0().compute_global_transform(start.0) else {
+        return;
+    };
+    let mut player = player_placement.p1();
+    let Ok((mut transform, mut velocity, mut fall)) = player.get_single_mut() else {
+        return;
+    };
+    // Only keep the yaw of the marker so the player stays upright
+    let (yaw, _, _) = start.rotation().to_euler(EulerRot::YXZ);
+    transform.translation = start.translation();
+    transform.rotation = Quat::from_rotation_y(yaw);
+    velocity.0 = Vec3::ZERO;
+    fall.reset();
+    info!("Player placed at level start {}", start.translation());
+}
+
 // Plugin for player functionality
 pub struct PlayerPlugin;
 
@@ -118,7 +288,9 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
         .add_systems(Startup, setup_player)
-        .add_systems(Update, (player_controller, update_player_stats));
+        .add_event::<PlayerLanded>()
+        .add_event::<PlayerDied>()
+        .add_systems(Update, (player_controller, update_player_stats, place_player_at_start, apply_fall_damage, detect_player_death.after(apply_fall_damage)));
         // Animation control is now handled in animation.rs
     }
 }
\ No newline at end of file
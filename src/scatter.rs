@@ -0,0 +1,354 @@
+use avian3d::prelude::RigidBody;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+        render_resource::PrimitiveTopology,
+    },
+    transform::TransformSystem,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::physics::{GameLayer, SpawnedByLevel};
+use crate::world::{LevelEntity, LevelManifest, LevelScene, LevelShape, LevelTransform};
+
+// Upper bound on the instances of one layer on one surface, dense grass on a big terrain adds up
+const DEFAULT_MAX_INSTANCES: usize = 20000;
+// Samples tried per wanted instance before giving up, steep or crowded surfaces reject most of them
+const MAX_ATTEMPTS_PER_INSTANCE: usize = 8;
+
+pub struct ScatterPlugin;
+
+impl Plugin for ScatterPlugin {
+    fn build(&self, app: &mut App) {
+        // Surfaces are sampled in world space, so wait for their transforms to be propagated
+        app.add_systems(PostUpdate, scatter_on_surfaces.after(TransformSystem::TransformPropagate));
+    }
+}
+
+// Mesh that scatter layers with the same surface tag place things on. Added to terrains,
+// manifest primitives with a scatter_surface, and glb meshes with BMeshExtras::scatter_surface.
+#[derive(Component)]
+pub struct ScatterSurface(pub String);
+
+// Put on a surface once its layers have been scattered
+#[derive(Component)]
+struct Scattered;
+
+// Thing placed by a scatter layer
+#[derive(Component)]
+pub struct ScatterInstance;
+
+/// A kind of foliage or prop spread over the surfaces with a tag, set in a level manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScatterLayer {
+    pub name: String,
+    pub surface: String,
+    pub model: ScatterModel,
+    #[serde(default)]
+    pub seed: u64,
+    // Attempts per square meter of surface, before the rules below throw some away
+    pub density: f32,
+    // Steepest ground, in degrees, that still gets instances
+    #[serde(default = "default_max_slope")]
+    pub max_slope: f32,
+    // No two instances of this layer are closer than this
+    #[serde(default)]
+    pub min_distance: f32,
+    // Places kept clear, like paths, the player start or around buildings
+    #[serde(default)]
+    pub exclusions: Vec<ScatterExclusion>,
+    #[serde(default = "default_scale_range")]
+    pub scale: [f32; 2],
+    // Tilt with the ground like grass, instead of standing upright like trees
+    #[serde(default)]
+    pub align_to_surface: bool,
+    // Static collider for large props, scaled with the instance
+    pub collider: Option<LevelShape>,
+    pub max_instances: Option<usize>,
+}
+
+fn default_max_slope() -> f32 {
+    35.0
+}
+
+fn default_scale_range() -> [f32; 2] {
+    [0.8, 1.2]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScatterExclusion {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ScatterModel {
+    // e.g. "models/tree.glb#Scene0"
+    Scene { path: String, #[serde(default)] transform: LevelTransform },
+    Primitive { shape: LevelShape, color: [f32; 3] },
+    // Two crossed quads, cheap enough for thousands of tufts
+    Grass { width: f32, height: f32, color: [f32; 3] },
+}
+
+// xorshift, seeded per layer so levels scatter the same way every time
+struct ScatterRng(u64);
+
+impl ScatterRng {
+    fn new(seed: u64) -> Self {
+        Self((seed ^ 0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+// World space triangles of a surface mesh, with their areas summed up for weighted picking
+struct SurfaceTriangles {
+    triangles: Vec<[Vec3; 3]>,
+    cumulative_area: Vec<f32>,
+}
+
+impl SurfaceTriangles {
+    fn from_mesh(mesh: &Mesh, transform: &GlobalTransform) -> Option<Self> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+        let positions: Vec<Vec3> = positions
+            .iter()
+            .map(|position| transform.transform_point(Vec3::from_array(*position)))
+            .collect();
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        let mut triangles = Vec::with_capacity(indices.len() / 3);
+        let mut cumulative_area = Vec::with_capacity(indices.len() / 3);
+        let mut total = 0.0;
+        for triangle in indices.chunks_exact(3) {
+            let corners = [positions[triangle[0]], positions[triangle[1]], positions[triangle[2]]];
+            total += (corners[1] - corners[0]).cross(corners[2] - corners[0]).length() * 0.5;
+            triangles.push(corners);
+            cumulative_area.push(total);
+        }
+        Some(Self { triangles, cumulative_area })
+    }
+
+    fn area(&self) -> f32 {
+        self.cumulative_area.last().copied().unwrap_or(0.0)
+    }
+
+    // Uniformly random point on the surface and the normal there
+    fn sample(&self, rng: &mut ScatterRng) -> (Vec3, Vec3) {
+        let target = rng.next_f32() * self.area();
+        let index = self
+            .cumulative_area
+            .partition_point(|area| *area < target)
+            .min(self.triangles.len() - 1);
+        let [a, b, c] = self.triangles[index];
+
+        let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+        if u + v > 1.0 {
+            u = 1.0 - u;
+            v = 1.0 - v;
+        }
+        let normal = (b - a).cross(c - a).normalize_or(Vec3::Y);
+        // Surfaces are walked on from above, so flip normals of triangles wound the other way
+        let normal = if normal.y < 0.0 { -normal } else { normal };
+        (a + (b - a) * u + (c - a) * v, normal)
+    }
+}
+
+// Accepted positions bucketed by min_distance sized cells, to check spacing without comparing every pair
+struct SpacingGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Vec3>>,
+}
+
+impl SpacingGrid {
+    fn new(min_distance: f32) -> Self {
+        Self {
+            cell_size: min_distance.max(0.01),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, position: Vec3) -> (i32, i32) {
+        ((position.x / self.cell_size).floor() as i32, (position.z / self.cell_size).floor() as i32)
+    }
+
+    fn is_free(&self, position: Vec3, min_distance: f32) -> bool {
+        let (x, z) = self.cell(position);
+        (-1..=1).all(|dx| {
+            (-1..=1).all(|dz| {
+                self.cells
+                    .get(&(x + dx, z + dz))
+                    .is_none_or(|points| points.iter().all(|point| point.distance(position) >= min_distance))
+            })
+        })
+    }
+
+    fn insert(&mut self, position: Vec3) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push(position);
+    }
+}
+
+fn grass_mesh(width: f32, height: f32) -> Mesh {
+    let half = width * 0.5;
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    for direction in [Vec3::X, Vec3::Z] {
+        let side = direction * half;
+        let normal = direction.cross(Vec3::Y);
+        let base = positions.len() as u32;
+        for (corner, uv) in [
+            (-side, [0.0, 1.0]),
+            (side, [1.0, 1.0]),
+            (side + Vec3::Y * height, [1.0, 0.0]),
+            (-side + Vec3::Y * height, [0.0, 0.0]),
+        ] {
+            positions.push(corner.to_array());
+            normals.push(normal.to_array());
+            uvs.push(uv);
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+// What gets spawned for every instance of a layer. Mesh and material handles are shared so
+// bevy draws all instances of a layer in one instanced batch.
+enum ScatterVisual {
+    Scene(Handle<Scene>, Transform),
+    Mesh(Handle<Mesh>, Handle<StandardMaterial>),
+}
+
+fn layer_visual(
+    model: &ScatterModel,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    asset_server: &AssetServer,
+) -> ScatterVisual {
+    match model {
+        ScatterModel::Scene { path, transform } => ScatterVisual::Scene(asset_server.load(path), Transform::from(transform)),
+        ScatterModel::Primitive { shape, color: [r, g, b] } => ScatterVisual::Mesh(
+            meshes.add(shape.mesh()),
+            materials.add(Color::srgb(*r, *g, *b)),
+        ),
+        ScatterModel::Grass { width, height, color: [r, g, b] } => ScatterVisual::Mesh(
+            meshes.add(grass_mesh(*width, *height)),
+            materials.add(StandardMaterial {
+                base_color: Color::srgb(*r, *g, *b),
+                perceptual_roughness: 0.9,
+                // Seen from both sides
+                cull_mode: None,
+                double_sided: true,
+                ..default()
+            }),
+        ),
+    }
+}
+
+fn scatter_on_surfaces(
+    mut commands: Commands,
+    manifest: Res<LevelManifest>,
+    surfaces: Query<(Entity, &ScatterSurface, &Mesh3d, &GlobalTransform), Without<Scattered>>,
+    parents: Query<&Parent>,
+    level_scenes: Query<(), With<LevelScene>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (surface_entity, surface, mesh, transform) in &surfaces {
+        let layers: Vec<&ScatterLayer> = manifest.scatter.iter().filter(|layer| layer.surface == surface.0).collect();
+        if layers.is_empty() {
+            commands.entity(surface_entity).insert(Scattered);
+            continue;
+        }
+        // glb meshes can show up before their mesh asset is ready, try again next frame
+        let Some(triangles) = meshes.get(&mesh.0).and_then(|mesh| SurfaceTriangles::from_mesh(mesh, transform)) else {
+            continue;
+        };
+        commands.entity(surface_entity).insert(Scattered);
+        if triangles.area() <= 0.0 {
+            continue;
+        }
+        // Instances on a glb go away with it when it's hot reloaded
+        let owner_scene = parents.iter_ancestors(surface_entity).find(|ancestor| level_scenes.contains(*ancestor));
+
+        for layer in layers {
+            let mut rng = ScatterRng::new(layer.seed);
+            let mut spacing = SpacingGrid::new(layer.min_distance);
+            let visual = layer_visual(&layer.model, &mut meshes, &mut materials, &asset_server);
+            let max_slope = layer.max_slope.to_radians().cos();
+            let wanted = ((triangles.area() * layer.density) as usize)
+                .min(layer.max_instances.unwrap_or(DEFAULT_MAX_INSTANCES));
+
+            let mut placed = 0;
+            let mut attempts = 0;
+            while placed < wanted && attempts < wanted * MAX_ATTEMPTS_PER_INSTANCE {
+                attempts += 1;
+                let (position, normal) = triangles.sample(&mut rng);
+                if normal.y < max_slope {
+                    continue;
+                }
+                if layer.exclusions.iter().any(|exclusion| exclusion.center.distance(position) < exclusion.radius) {
+                    continue;
+                }
+                if layer.min_distance > 0.0 {
+                    if !spacing.is_free(position, layer.min_distance) {
+                        continue;
+                    }
+                    spacing.insert(position);
+                }
+
+                let yaw = Quat::from_rotation_y(rng.next_f32() * std::f32::consts::TAU);
+                let tilt = if layer.align_to_surface { Quat::from_rotation_arc(Vec3::Y, normal) } else { Quat::IDENTITY };
+                let scale = layer.scale[0].lerp(layer.scale[1], rng.next_f32());
+                let instance_transform = Transform::from_translation(position)
+                    .with_rotation(tilt * yaw)
+                    .with_scale(Vec3::splat(scale));
+
+                let mut instance = match &visual {
+                    ScatterVisual::Scene(scene, offset) => {
+                        commands.spawn((SceneRoot(scene.clone()), instance_transform * *offset, ScatterInstance))
+                    }
+                    ScatterVisual::Mesh(mesh, material) => commands.spawn((
+                        Mesh3d(mesh.clone()),
+                        MeshMaterial3d(material.clone()),
+                        instance_transform,
+                        ScatterInstance,
+                    )),
+                };
+                if let Some(collider) = &layer.collider {
+                    instance.insert((RigidBody::Static, collider.collider(), GameLayer::environment()));
+                }
+                match owner_scene {
+                    Some(scene) => instance.insert(SpawnedByLevel(scene)),
+                    None => instance.insert(LevelEntity),
+                };
+                placed += 1;
+            }
+            if placed < wanted {
+                info!("Scattered {} of {} {} on {}, ran out of free spots", placed, wanted, layer.name, surface.0);
+            } else {
+                info!("Scattered {} {} on {}", placed, layer.name, surface.0);
+            }
+        }
+    }
+}
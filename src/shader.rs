@@ -5,12 +5,21 @@ use bevy::{
 const AURORA_SHADER_PATH: &str = "shaders/aurora.wgsl";
 
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-struct AuroraShaderMaterial {}
+pub struct AuroraShaderMaterial {
+    // 0 during the day, 1 at night, set by lighting::update_sky
+    #[uniform(0)]
+    pub night_fade: f32,
+}
 
 impl Material for AuroraShaderMaterial{
     fn fragment_shader() -> ShaderRef {
         AURORA_SHADER_PATH.into()
     }
+
+    // Blended so it can fade out over the day sky
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
 }
 
 fn setup(
@@ -20,7 +29,7 @@ fn setup(
 ){
       commands.spawn((
             Mesh3d(meshes.add(Sphere::new(500.0))),
-            MeshMaterial3d(materials.add(AuroraShaderMaterial {})),
+            MeshMaterial3d(materials.add(AuroraShaderMaterial { night_fade: 1.0 })),
             Transform::from_xyz(500.0, 500.0, 1000.0).looking_at(Vec3::ZERO, Vec3::Y),
       ));
 }
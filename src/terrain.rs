@@ -0,0 +1,412 @@
+use avian3d::prelude::{Collider, RigidBody};
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::physics::GameLayer;
+use crate::scatter::ScatterSurface;
+
+// Vertices along each side, above this the heightfield takes too long to generate and erode
+const MAX_TERRAIN_RESOLUTION: u32 = 1025;
+
+/// Seeded heightfield ground, set in a level manifest instead of a Plane primitive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerrainConfig {
+    #[serde(default)]
+    pub seed: u32,
+    // Width and depth in meters, the terrain is centered on its translation
+    #[serde(default = "default_terrain_size")]
+    pub size: f32,
+    // Vertices along each side
+    #[serde(default = "default_terrain_resolution")]
+    pub resolution: u32,
+    // Height of a noise layer with an amplitude of 1
+    #[serde(default = "default_terrain_height")]
+    pub height: f32,
+    #[serde(default = "default_noise_layers")]
+    pub layers: Vec<NoiseLayer>,
+    // Thermal erosion passes, they wear down slopes steeper than talus_angle
+    #[serde(default = "default_erosion_iterations")]
+    pub erosion_iterations: u32,
+    #[serde(default = "default_talus_angle")]
+    pub talus_angle: f32,
+    // Radius around the center that's kept flat at height 0, so the player has somewhere to stand
+    #[serde(default = "default_flat_radius")]
+    pub flat_radius: f32,
+    // How many times the texture repeats across the terrain
+    #[serde(default = "default_uv_scale")]
+    pub uv_scale: f32,
+    pub material: Option<String>,
+    #[serde(default)]
+    pub translation: Vec3,
+    // Tag scatter layers use to place things on the terrain
+    #[serde(default = "default_scatter_surface")]
+    pub scatter_surface: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoiseLayer {
+    // Features per meter
+    pub frequency: f32,
+    pub amplitude: f32,
+}
+
+impl TerrainConfig {
+    // A heightfield needs at least 2x2 vertices over a real area, anything less makes a
+    // degenerate collider
+    pub fn validate(&self) -> Result<(), (String, String)> {
+        if !(self.size > 0.0 && self.size.is_finite()) {
+            return Err(("terrain.size".to_string(), format!("size must be positive, got {}", self.size)));
+        }
+        if !(2..=MAX_TERRAIN_RESOLUTION).contains(&self.resolution) {
+            return Err((
+                "terrain.resolution".to_string(),
+                format!("resolution must be between 2 and {}, got {}", MAX_TERRAIN_RESOLUTION, self.resolution),
+            ));
+        }
+        if !(self.talus_angle > 0.0 && self.talus_angle < 90.0) {
+            return Err((
+                "terrain.talus_angle".to_string(),
+                format!("talus_angle must be between 0 and 90 degrees, got {}", self.talus_angle),
+            ));
+        }
+        if self.uv_scale <= 0.0 {
+            return Err(("terrain.uv_scale".to_string(), format!("uv_scale must be positive, got {}", self.uv_scale)));
+        }
+        Ok(())
+    }
+}
+
+fn default_terrain_size() -> f32 {
+    200.0
+}
+
+fn default_terrain_resolution() -> u32 {
+    129
+}
+
+fn default_terrain_height() -> f32 {
+    25.0
+}
+
+fn default_noise_layers() -> Vec<NoiseLayer> {
+    vec![
+        NoiseLayer { frequency: 0.01, amplitude: 1.0 },
+        NoiseLayer { frequency: 0.03, amplitude: 0.35 },
+        NoiseLayer { frequency: 0.09, amplitude: 0.1 },
+    ]
+}
+
+fn default_erosion_iterations() -> u32 {
+    20
+}
+
+fn default_talus_angle() -> f32 {
+    40.0
+}
+
+fn default_flat_radius() -> f32 {
+    10.0
+}
+
+fn default_uv_scale() -> f32 {
+    32.0
+}
+
+fn default_scatter_surface() -> String {
+    "terrain".to_string()
+}
+
+// Heights on a square grid, indexed heights[x][z] like avian's heightfield collider
+pub struct Heightmap {
+    pub heights: Vec<Vec<f32>>,
+    pub size: f32,
+}
+
+impl Heightmap {
+    pub fn generate(config: &TerrainConfig) -> Self {
+        let resolution = config.resolution.max(2) as usize;
+        let step = config.size / (resolution - 1) as f32;
+        let half_size = config.size * 0.5;
+
+        let mut heights = vec![vec![0.0; resolution]; resolution];
+        for (x, column) in heights.iter_mut().enumerate() {
+            for (z, height) in column.iter_mut().enumerate() {
+                let position = Vec2::new(x as f32 * step - half_size, z as f32 * step - half_size);
+                *height = config
+                    .layers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, layer)| {
+                        // Every layer gets its own seed so they don't line up
+                        let seed = config.seed.wrapping_add(index as u32 * 1013);
+                        value_noise(position * layer.frequency, seed) * layer.amplitude
+                    })
+                    .sum::<f32>()
+                    * config.height;
+            }
+        }
+
+        let mut heightmap = Self { heights, size: config.size };
+        heightmap.erode(config.erosion_iterations, config.talus_angle.to_radians().tan() * step);
+        heightmap.flatten_center(config.flat_radius, step);
+        heightmap
+    }
+
+    fn resolution(&self) -> usize {
+        self.heights.len()
+    }
+
+    fn step(&self) -> f32 {
+        self.size / (self.resolution() - 1) as f32
+    }
+
+    // Thermal erosion: material slides from a cell to its lower neighbours while the
+    // difference is steeper than the talus, which rounds off peaks and spreads out cliffs
+    fn erode(&mut self, iterations: u32, talus: f32) {
+        let resolution = self.resolution();
+        for _ in 0..iterations {
+            let mut deltas = vec![vec![0.0; resolution]; resolution];
+            for x in 0..resolution {
+                for z in 0..resolution {
+                    let height = self.heights[x][z];
+                    let mut lower = Vec::with_capacity(4);
+                    for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                        let nx = x as i32 + dx;
+                        let nz = z as i32 + dz;
+                        if nx < 0 || nz < 0 || nx >= resolution as i32 || nz >= resolution as i32 {
+                            continue;
+                        }
+                        let difference = height - self.heights[nx as usize][nz as usize];
+                        if difference > talus {
+                            lower.push((nx as usize, nz as usize, difference));
+                        }
+                    }
+                    let total: f32 = lower.iter().map(|(_, _, difference)| difference - talus).sum();
+                    if total <= 0.0 {
+                        continue;
+                    }
+                    // Move half of the largest excess, split by how much lower each neighbour is
+                    let largest = lower.iter().map(|(_, _, difference)| difference - talus).fold(0.0, f32::max);
+                    for (nx, nz, difference) in lower {
+                        let share = 0.5 * largest * (difference - talus) / total;
+                        deltas[x][z] -= share;
+                        deltas[nx][nz] += share;
+                    }
+                }
+            }
+            for (column, column_deltas) in self.heights.iter_mut().zip(deltas) {
+                for (height, delta) in column.iter_mut().zip(column_deltas) {
+                    *height += delta;
+                }
+            }
+        }
+    }
+
+    // The noise is all above 0, so scaling it down would dig a crater. Instead blend towards the
+    // average height around the flat area, then shift everything so the flat area is at 0.
+    fn flatten_center(&mut self, radius: f32, step: f32) {
+        if radius <= 0.0 {
+            return;
+        }
+        let half_size = self.size * 0.5;
+        let distance = |x: usize, z: usize| Vec2::new(x as f32 * step - half_size, z as f32 * step - half_size).length();
+
+        // Blend back into the terrain over the same distance again
+        let (rim_total, rim_count) = self
+            .heights
+            .iter()
+            .enumerate()
+            .flat_map(|(x, column)| column.iter().enumerate().map(move |(z, height)| (x, z, *height)))
+            .filter(|(x, z, _)| (radius..=radius * 2.0).contains(&distance(*x, *z)))
+            .fold((0.0, 0), |(total, count), (_, _, height)| (total + height, count + 1));
+        let rim_height = if rim_count > 0 { rim_total / rim_count as f32 } else { 0.0 };
+
+        for (x, column) in self.heights.iter_mut().enumerate() {
+            for (z, height) in column.iter_mut().enumerate() {
+                let t = ((distance(x, z) - radius) / radius).clamp(0.0, 1.0);
+                *height = rim_height.lerp(*height, t * t * (3.0 - 2.0 * t)) - rim_height;
+            }
+        }
+    }
+
+    pub fn normal(&self, x: usize, z: usize) -> Vec3 {
+        let last = self.resolution() - 1;
+        let left = self.heights[x.saturating_sub(1)][z];
+        let right = self.heights[(x + 1).min(last)][z];
+        let back = self.heights[x][z.saturating_sub(1)];
+        let front = self.heights[x][(z + 1).min(last)];
+        Vec3::new(left - right, 2.0 * self.step(), back - front).normalize()
+    }
+
+    pub fn mesh(&self, uv_scale: f32) -> Mesh {
+        let resolution = self.resolution();
+        let step = self.step();
+        let half_size = self.size * 0.5;
+
+        let mut positions = Vec::with_capacity(resolution * resolution);
+        let mut normals = Vec::with_capacity(resolution * resolution);
+        let mut uvs = Vec::with_capacity(resolution * resolution);
+        for x in 0..resolution {
+            for z in 0..resolution {
+                positions.push([x as f32 * step - half_size, self.heights[x][z], z as f32 * step - half_size]);
+                normals.push(self.normal(x, z).to_array());
+                let uv = Vec2::new(x as f32, z as f32) / (resolution - 1) as f32 * uv_scale;
+                uvs.push(uv.to_array());
+            }
+        }
+
+        let vertex = |x: usize, z: usize| (x * resolution + z) as u32;
+        let mut indices = Vec::with_capacity((resolution - 1) * (resolution - 1) * 6);
+        for x in 0..resolution - 1 {
+            for z in 0..resolution - 1 {
+                let (a, b, c, d) = (vertex(x, z), vertex(x, z + 1), vertex(x + 1, z), vertex(x + 1, z + 1));
+                indices.extend_from_slice(&[a, b, c, c, b, d]);
+            }
+        }
+
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+            .with_inserted_indices(Indices::U32(indices))
+    }
+
+    pub fn collider(&self) -> Collider {
+        Collider::heightfield(self.heights.clone(), Vec3::new(self.size, 1.0, self.size))
+    }
+}
+
+fn hash(x: i32, z: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (z as u32).wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+
+// Smoothly interpolated lattice noise in 0..1
+fn value_noise(point: Vec2, seed: u32) -> f32 {
+    let cell = point.floor();
+    let fraction = point - cell;
+    let (x, z) = (cell.x as i32, cell.y as i32);
+    let u = fraction * fraction * (Vec2::splat(3.0) - 2.0 * fraction);
+
+    let a = hash(x, z, seed);
+    let b = hash(x + 1, z, seed);
+    let c = hash(x, z + 1, seed);
+    let d = hash(x + 1, z + 1, seed);
+    a.lerp(b, u.x).lerp(c.lerp(d, u.x), u.y)
+}
+
+// Generate the terrain and spawn it as a static body with a heightfield collider
+pub fn spawn_terrain(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    material: Handle<StandardMaterial>,
+    config: &TerrainConfig,
+) -> Entity {
+    let heightmap = Heightmap::generate(config);
+    info!("Generated {}m terrain with seed {}", config.size, config.seed);
+
+    commands
+        .spawn((
+            Name::new("Terrain"),
+            Mesh3d(meshes.add(heightmap.mesh(config.uv_scale))),
+            MeshMaterial3d(material),
+            Transform::from_translation(config.translation),
+            RigidBody::Static,
+            heightmap.collider(),
+            GameLayer::environment(),
+            ScatterSurface(config.scatter_surface.clone()),
+        ))
+        .id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: &str) -> TerrainConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    // Largest height difference between neighbouring vertices
+    fn steepest_step(heightmap: &Heightmap) -> f32 {
+        let heights = &heightmap.heights;
+        let mut steepest: f32 = 0.0;
+        for x in 0..heights.len() {
+            for z in 0..heights.len() {
+                if x + 1 < heights.len() {
+                    steepest = steepest.max((heights[x][z] - heights[x + 1][z]).abs());
+                }
+                if z + 1 < heights.len() {
+                    steepest = steepest.max((heights[x][z] - heights[x][z + 1]).abs());
+                }
+            }
+        }
+        steepest
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(config("{}").validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        for (json, path) in [
+            (r#"{"size": 0.0}"#, "terrain.size"),
+            (r#"{"size": -10.0}"#, "terrain.size"),
+            (r#"{"resolution": 1}"#, "terrain.resolution"),
+            (r#"{"resolution": 2000}"#, "terrain.resolution"),
+            (r#"{"talus_angle": 0.0}"#, "terrain.talus_angle"),
+            (r#"{"talus_angle": 90.0}"#, "terrain.talus_angle"),
+            (r#"{"uv_scale": 0.0}"#, "terrain.uv_scale"),
+        ] {
+            let err = config(json).validate().unwrap_err();
+            assert_eq!(err.0, path, "{}", json);
+        }
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_terrain() {
+        let terrain = config(r#"{"seed": 3, "size": 64.0, "resolution": 33}"#);
+        assert_eq!(Heightmap::generate(&terrain).heights, Heightmap::generate(&terrain).heights);
+
+        let other = config(r#"{"seed": 4, "size": 64.0, "resolution": 33}"#);
+        assert_ne!(Heightmap::generate(&terrain).heights, Heightmap::generate(&other).heights);
+    }
+
+    #[test]
+    fn center_is_flat_and_meets_the_rim() {
+        for seed in 0..4 {
+            let flat = Heightmap::generate(&config(&format!(
+                r#"{{"seed": {}, "size": 64.0, "resolution": 33, "flat_radius": 10.0}}"#,
+                seed
+            )));
+            let raw = Heightmap::generate(&config(&format!(
+                r#"{{"seed": {}, "size": 64.0, "resolution": 33, "flat_radius": 0.0}}"#,
+                seed
+            )));
+
+            let step = flat.step();
+            let distance = |x: usize, z: usize| Vec2::new(x as f32 * step - 32.0, z as f32 * step - 32.0).length();
+            let mut offsets = Vec::new();
+            for x in 0..33 {
+                for z in 0..33 {
+                    if distance(x, z) <= 10.0 {
+                        assert!(flat.heights[x][z].abs() < 1e-4, "seed {} center isn't flat at {} {}", seed, x, z);
+                    } else if distance(x, z) >= 20.0 {
+                        offsets.push(flat.heights[x][z] - raw.heights[x][z]);
+                    }
+                }
+            }
+            // Past the blend the terrain is only shifted, and the blend is no cliff
+            assert!(offsets.iter().all(|offset| (offset - offsets[0]).abs() < 1e-3), "seed {}", seed);
+            assert!(steepest_step(&flat) <= steepest_step(&raw) * 1.5, "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use avian3d::prelude::LinearVelocity;
+use bevy::{prelude::*, transform::helper::TransformHelper};
+
+use crate::physics::{BMarker, BTrigger, LevelMarker, SpawnedByLevel};
+use crate::player::{FallDamage, Player};
+use crate::triggers::TriggerEntered;
+use crate::world::{spawn_level, unload_level, LevelEntity, LevelRegistry, LevelSelection};
+
+// Seconds to fade to black and back
+const FADE_TIME: f32 = 0.5;
+// How long the player is held at an arrival position so the level's colliders are in place
+const ARRIVAL_SETTLE_TIME: f32 = 0.5;
+// How long to wait for the destination marker of the new level before giving up on it
+const LOAD_TIMEOUT: f32 = 5.0;
+
+pub struct TransitionsPlugin;
+
+impl Plugin for TransitionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelTransition>()
+            .add_systems(Startup, spawn_fade_overlay)
+            .add_systems(Update, (
+                start_portal_transitions,
+                run_level_transition,
+            ).chain());
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TransitionPhase {
+    #[default]
+    Idle,
+    FadingOut,
+    // The new level is spawned and we're waiting for its destination marker
+    Loading,
+    FadingIn,
+}
+
+// Level change in progress. Only level entities are swapped, the player, PlayerProgress,
+// GameUI and achievements carry over as they are.
+#[derive(Resource, Default)]
+pub struct LevelTransition {
+    pub phase: TransitionPhase,
+    target: String,
+    destination: Option<String>,
+    // Exact place to put the player instead of a marker, e.g. a rested checkpoint
+    arrival: Option<Transform>,
+    timer: f32,
+    // Where the player is held while the new level loads, so they don't fall into the void
+    held_position: Vec3,
+}
+
+impl LevelTransition {
+    // Go to the Destination marker with the given id, or the level's PlayerStart
+    pub fn to_marker(level: &str, destination: Option<String>) -> Self {
+        Self {
+            phase: TransitionPhase::FadingOut,
+            target: level.to_string(),
+            destination,
+            arrival: None,
+            timer: 0.0,
+            held_position: Vec3::ZERO,
+        }
+    }
+
+    // (Re)load the level and put the player at the given transform
+    pub fn to_transform(level: &str, arrival: Transform) -> Self {
+        Self {
+            arrival: Some(arrival),
+            ..Self::to_marker(level, None)
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.phase != TransitionPhase::Idle
+    }
+}
+
+// Full screen black node faded in and out around level changes
+#[derive(Component)]
+pub struct FadeOverlay;
+
+fn spawn_fade_overlay(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::NONE),
+        GlobalZIndex(100),
+        FadeOverlay,
+    ));
+}
+
+fn start_portal_transitions(
+    mut entered_events: EventReader<TriggerEntered>,
+    mut transition: ResMut<LevelTransition>,
+    registry: Res<LevelRegistry>,
+) {
+    for event in entered_events.read() {
+        let BTrigger::Portal { level, destination } = &event.kind else {
+            continue;
+        };
+        if transition.is_active() {
+            continue;
+        }
+        if !registry.contains(level) {
+            warn!("Portal leads to unknown level {}, known levels: {:?}", level, registry.levels);
+            continue;
+        }
+        info!("Entering portal to {}", level);
+        *transition = LevelTransition::to_marker(level, destination.clone());
+    }
+}
+
+fn run_level_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut transition: ResMut<LevelTransition>,
+    registry: Res<LevelRegistry>,
+    mut selection: ResMut<LevelSelection>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+    level_entities: Query<Entity, With<LevelEntity>>,
+    level_owned: Query<(Entity, &SpawnedByLevel)>,
+    markers: Query<(Entity, &LevelMarker)>,
+    mut player_placement: ParamSet<(
+        TransformHelper,
+        Query<(&mut Transform, &mut LinearVelocity, &mut FallDamage), With<Player>>,
+    )>,
+    mut overlay_query: Query<&mut BackgroundColor, With<FadeOverlay>>,
+) {
+    transition.timer += time.delta_secs();
+    let fade = (transition.timer / FADE_TIME).clamp(0.0, 1.0);
+
+    match transition.phase {
+        TransitionPhase::Idle => return,
+        TransitionPhase::FadingOut => {
+            set_fade(&mut overlay_query, fade);
+            if fade < 1.0 {
+                return;
+            }
+
+            // Load the manifest before unloading, so a broken level leaves us where we were
+            let manifest = match registry.load(&transition.target) {
+                Ok(manifest) => manifest,
+                Err(err) => {
+                    error!("Failed to load level manifest {}", err);
+                    transition.phase = TransitionPhase::FadingIn;
+                    transition.timer = 0.0;
+                    return;
+                }
+            };
+
+            unload_level(&mut commands, &level_entities, &level_owned);
+            spawn_level(&mut commands, &mut meshes, &mut materials, &asset_server, &manifest);
+            commands.insert_resource(manifest);
+            selection.name = transition.target.clone();
+
+            if let Ok((transform, _, _)) = player_placement.p1().get_single() {
+                transition.held_position = transform.translation;
+            }
+            transition.phase = TransitionPhase::Loading;
+            transition.timer = 0.0;
+        }
+        TransitionPhase::Loading => {
+            // Destination markers of glb levels only show up once their scene is ready
+            let destination = markers.iter().find(|(_, marker)| match (&marker.0, &transition.destination) {
+                (BMarker::Destination { id }, Some(destination)) => id == destination,
+                (BMarker::PlayerStart, None) => true,
+                _ => false,
+            });
+            let placement = match transition.arrival {
+                // Wait a moment so the player doesn't drop through a level that's still loading
+                Some(arrival) if transition.timer >= ARRIVAL_SETTLE_TIME => Some(GlobalTransform::from(arrival)),
+                Some(_) => None,
+                None => destination.and_then(|(entity, _)| player_placement.p0().compute_global_transform(entity).ok()),
+            };
+
+            let mut player = player_placement.p1();
+            let Ok((mut transform, mut velocity, mut fall)) = player.get_single_mut() else {
+                return;
+            };
+            // Whatever the player was falling at before the teleport shouldn't hurt on arrival
+            velocity.0 = Vec3::ZERO;
+            fall.reset();
+
+            if let Some(placement) = placement {
+                // Only keep the yaw of the marker so the player stays upright
+                let (yaw, _, _) = placement.rotation().to_euler(EulerRot::YXZ);
+                transform.translation = placement.translation();
+                transform.rotation = Quat::from_rotation_y(yaw);
+                info!("Player arrived in {} at {}", transition.target, placement.translation());
+            } else if let Some(arrival) = transition.arrival {
+                transform.translation = arrival.translation;
+                return;
+            } else if transition.timer < LOAD_TIMEOUT {
+                transform.translation = transition.held_position;
+                return;
+            } else {
+                warn!(
+                    "Level {} has no destination marker {:?}, leaving the player where they were",
+                    transition.target, transition.destination
+                );
+            }
+            transition.phase = TransitionPhase::FadingIn;
+            transition.timer = 0.0;
+        }
+        TransitionPhase::FadingIn => {
+            set_fade(&mut overlay_query, 1.0 - fade);
+            if fade >= 1.0 {
+                transition.phase = TransitionPhase::Idle;
+            }
+        }
+    }
+}
+
+fn set_fade(overlay_query: &mut Query<&mut BackgroundColor, With<FadeOverlay>>, alpha: f32) {
+    for mut background in overlay_query.iter_mut() {
+        background.0 = Color::BLACK.with_alpha(alpha);
+    }
+}
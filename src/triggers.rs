@@ -0,0 +1,237 @@
+use avian3d::prelude::{ColliderParent, CollisionEnded, CollisionStarted};
+use bevy::prelude::*;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::achievements::AchievementEvent;
+use crate::physics::BTrigger;
+use crate::player::Player;
+use crate::progression::WorldArea;
+use crate::world::LevelSelection;
+
+// How many areas have to be discovered for the "explorer" achievement
+const AREAS_FOR_EXPLORER: usize = 5;
+
+pub struct TriggersPlugin;
+
+impl Plugin for TriggersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DiscoveredAreas>()
+            .add_event::<TriggerEntered>()
+            .add_event::<TriggerExited>()
+            .add_systems(Update, (
+                setup_area_volumes,
+                detect_trigger_contacts,
+                handle_kill_zones,
+                handle_area_discovery,
+                handle_cutscenes,
+                apply_damage_over_time,
+                handle_discovery_banner,
+            ));
+    }
+}
+
+// Sensor volume authored in the level, see BMeshExtras::trigger
+#[derive(Component)]
+pub struct TriggerVolume {
+    pub kind: BTrigger,
+    pub player_inside: bool,
+}
+
+#[derive(Event)]
+pub struct TriggerEntered {
+    pub volume: Entity,
+    pub kind: BTrigger,
+}
+
+#[derive(Event)]
+pub struct TriggerExited {
+    pub volume: Entity,
+    pub kind: BTrigger,
+}
+
+// Areas found so far as (level, area id). Area volumes are despawned with their level, so
+// this is what keeps them discovered across level changes and respawns.
+#[derive(Resource, Default)]
+pub struct DiscoveredAreas(pub HashSet<(String, String)>);
+
+impl DiscoveredAreas {
+    pub fn contains(&self, level: &str, id: &str) -> bool {
+        self.0.contains(&(level.to_string(), id.to_string()))
+    }
+}
+
+// Text shown for a moment when entering a new area
+#[derive(Component)]
+pub struct DiscoveryBanner {
+    timer: Timer,
+}
+
+// Area volumes double as the WorldArea they describe
+fn setup_area_volumes(
+    mut commands: Commands,
+    volumes: Query<(Entity, &TriggerVolume), Added<TriggerVolume>>,
+    discovered: Res<DiscoveredAreas>,
+    level: Res<LevelSelection>,
+) {
+    for (entity, volume) in &volumes {
+        if let BTrigger::AreaDiscovery { id, name } = &volume.kind {
+            commands.entity(entity).insert(WorldArea {
+                id: id.clone(),
+                name: name.clone(),
+                discovered: discovered.contains(&level.name, id),
+            });
+        }
+    }
+}
+
+// Turn avian collision events between the player and sensor volumes into trigger events
+fn detect_trigger_contacts(
+    mut started: EventReader<CollisionStarted>,
+    mut ended: EventReader<CollisionEnded>,
+    mut volumes: Query<&mut TriggerVolume>,
+    collider_parents: Query<&ColliderParent>,
+    players: Query<(), With<Player>>,
+    mut entered_events: EventWriter<TriggerEntered>,
+    mut exited_events: EventWriter<TriggerExited>,
+) {
+    // The player's collider is a child of the player body, so look up the body it belongs to
+    let is_player = |entity: Entity| {
+        let body = collider_parents.get(entity).map_or(entity, |parent| parent.get());
+        players.contains(body)
+    };
+
+    for CollisionStarted(a, b) in started.read() {
+        let (volume, other) = if volumes.contains(*a) { (*a, *b) } else { (*b, *a) };
+        if !is_player(other) {
+            continue;
+        }
+        let Ok(mut trigger) = volumes.get_mut(volume) else {
+            continue;
+        };
+        trigger.player_inside = true;
+        entered_events.send(TriggerEntered {
+            volume,
+            kind: trigger.kind.clone(),
+        });
+    }
+
+    for CollisionEnded(a, b) in ended.read() {
+        let (volume, other) = if volumes.contains(*a) { (*a, *b) } else { (*b, *a) };
+        if !is_player(other) {
+            continue;
+        }
+        let Ok(mut trigger) = volumes.get_mut(volume) else {
+            continue;
+        };
+        trigger.player_inside = false;
+        exited_events.send(TriggerExited {
+            volume,
+            kind: trigger.kind.clone(),
+        });
+    }
+}
+
+fn handle_kill_zones(
+    mut entered_events: EventReader<TriggerEntered>,
+    mut player_query: Query<&mut Player>,
+) {
+    for event in entered_events.read() {
+        if let BTrigger::KillZone = event.kind {
+            if let Ok(mut player) = player_query.get_single_mut() {
+                player.health = 0.0;
+                info!("Player entered a kill zone");
+            }
+        }
+    }
+}
+
+fn handle_area_discovery(
+    mut commands: Commands,
+    mut entered_events: EventReader<TriggerEntered>,
+    mut areas: Query<&mut WorldArea>,
+    mut discovered: ResMut<DiscoveredAreas>,
+    level: Res<LevelSelection>,
+    mut achievement_events: EventWriter<AchievementEvent>,
+) {
+    for event in entered_events.read() {
+        let Ok(mut area) = areas.get_mut(event.volume) else {
+            continue;
+        };
+        if area.discovered {
+            continue;
+        }
+        area.discovered = true;
+        if !discovered.0.insert((level.name.clone(), area.id.clone())) {
+            continue;
+        }
+        info!("Discovered area: {} ({})", area.name, area.id);
+        let area_name = area.name.clone();
+
+        commands.spawn((
+            Text::new(area_name),
+            TextFont {
+                font_size: 40.0,
+                ..default()
+            },
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(30.0),
+                left: Val::Percent(45.0),
+                ..default()
+            },
+            DiscoveryBanner {
+                timer: Timer::from_seconds(3.0, TimerMode::Once),
+            },
+        ));
+
+        if discovered.0.len() == AREAS_FOR_EXPLORER {
+            achievement_events.send(AchievementEvent {
+                achievement_id: "explorer".to_string(),
+                progress_amount: None,
+            });
+        }
+    }
+}
+
+// Checkpoint volumes are handled by checkpoints.rs
+fn handle_cutscenes(
+    mut entered_events: EventReader<TriggerEntered>,
+) {
+    for event in entered_events.read() {
+        if let BTrigger::CutsceneStart { id } = &event.kind {
+            info!("Starting cutscene {}", id);
+        }
+    }
+}
+
+fn apply_damage_over_time(
+    volumes: Query<&TriggerVolume>,
+    mut player_query: Query<&mut Player>,
+    time: Res<Time>,
+) {
+    let Ok(mut player) = player_query.get_single_mut() else {
+        return;
+    };
+    for volume in &volumes {
+        if let BTrigger::DamageOverTime { damage_per_second } = volume.kind {
+            if volume.player_inside {
+                player.health = (player.health - damage_per_second * time.delta_secs()).max(0.0);
+            }
+        }
+    }
+}
+
+fn handle_discovery_banner(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut banners: Query<(Entity, &mut DiscoveryBanner)>,
+) {
+    for (entity, mut banner) in &mut banners {
+        banner.timer.tick(Duration::from_secs_f32(time.delta_secs()));
+
+        if banner.timer.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
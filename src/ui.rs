@@ -1,7 +1,5 @@
-use bevy::{
-    prelude::*,
-    input::keyboard::KeyCode,
-};
+use bevy::prelude::*;
+use crate::input::{Action, ActionState};
 use crate::player::Player;
 use bevy_lunex::{UiLunexDebugPlugin, UiLunexPlugin};
 
@@ -564,7 +562,7 @@ pub fn animate_shield_bar(
 
 // Debug controls for testing UI
 pub fn debug_ui_control(
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut player_query: Query<&mut Player>,
     mut ui_state: ResMut<GameUI>,
     time: Res<Time>,
@@ -575,31 +573,28 @@ pub fn debug_ui_control(
     // Modify player stats directly if available
     if let Ok(mut player) = player_query.get_single_mut() {
         // Health controls
-        if keyboard.pressed(KeyCode::KeyH) {
+        if actions.pressed(Action::DebugDamage) {
             player.health = (player.health - change_amount).max(0.0);
         }
-        if keyboard.pressed(KeyCode::KeyJ) {
+        // Healing doesn't bring the player back, only respawning does
+        if actions.pressed(Action::DebugHeal) && !player.is_dead {
             player.health = (player.health + change_amount).min(player.max_health);
         }
     }
     
     // Controls that modify UI state directly
     
-    // Add souls with S key
-    if keyboard.just_pressed(KeyCode::KeyS) {
-        ui_state.souls += 100;
-        println!("Souls gained! Total: {}", ui_state.souls);
-    }
+    // Souls come from npcs::debug_gain_souls, S used to add them here but it's also move back
     
     // Damage shield with K key
-    if keyboard.just_pressed(KeyCode::KeyK) {
+    if actions.just_pressed(Action::DebugShieldHit) {
         ui_state.shield = (ui_state.shield - 10.0).max(0.0);
         ui_state.last_shield_hit = time.elapsed_secs();
         println!("Shield hit! Remaining: {}", ui_state.shield);
     }
     
     // Recover shield with L key
-    if keyboard.just_pressed(KeyCode::KeyL) {
+    if actions.just_pressed(Action::DebugShieldRestore) {
         ui_state.shield = ui_state.max_shield;
         println!("Shield fully recovered!");
     }
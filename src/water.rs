@@ -0,0 +1,106 @@
+use avian3d::prelude::{AngularVelocity, ColliderAabb, Gravity, LinearVelocity, RigidBody};
+use bevy::prelude::*;
+
+use crate::player::Player;
+
+// How high the player's chest is above their origin, once it's under the surface they swim
+const SWIM_DEPTH: f32 = 1.0;
+// How quickly a swimming player is pulled back to the surface
+const SURFACE_PULL: f32 = 4.0;
+
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, apply_buoyancy)
+            .add_systems(Update, update_swimming);
+    }
+}
+
+// Sensor volume that pushes dynamic bodies up and slows them down. Added from BMeshExtras::water,
+// or by hand next to a Sensor collider. The top of its bounding box is the water surface.
+#[derive(Component)]
+pub struct WaterVolume {
+    pub buoyancy: f32,
+    pub linear_drag: f32,
+    pub angular_drag: f32,
+}
+
+// How much of the box is under the surface of the water box, 0 when they don't overlap
+fn submerged_fraction(water: &ColliderAabb, body: &ColliderAabb) -> f32 {
+    let overlaps_horizontally = body.max.x >= water.min.x
+        && body.min.x <= water.max.x
+        && body.max.z >= water.min.z
+        && body.min.z <= water.max.z;
+    if !overlaps_horizontally || body.min.y > water.max.y || body.max.y < water.min.y {
+        return 0.0;
+    }
+    let height = (body.max.y - body.min.y).max(0.001);
+    ((water.max.y - body.min.y) / height).clamp(0.0, 1.0)
+}
+
+// Runs on the fixed step like the physics it feeds, so floating doesn't depend on the frame rate
+fn apply_buoyancy(
+    waters: Query<(&WaterVolume, &ColliderAabb)>,
+    mut bodies: Query<(&RigidBody, &ColliderAabb, &mut LinearVelocity, &mut AngularVelocity), (Without<WaterVolume>, Without<Player>)>,
+    gravity: Res<Gravity>,
+    time: Res<Time<Fixed>>,
+) {
+    let dt = time.delta_secs();
+    for (rigid_body, body_aabb, mut linear_velocity, mut angular_velocity) in &mut bodies {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        for (water, water_aabb) in &waters {
+            let submerged = submerged_fraction(water_aabb, body_aabb);
+            if submerged <= 0.0 {
+                continue;
+            }
+            // Push against gravity in proportion to how deep the body is
+            linear_velocity.0 -= gravity.0 * water.buoyancy * submerged * dt;
+            linear_velocity.0 *= (1.0 - water.linear_drag * submerged * dt).max(0.0);
+            angular_velocity.0 *= (1.0 - water.angular_drag * submerged * dt).max(0.0);
+        }
+    }
+}
+
+// Switch the player to swimming while their chest is under water and keep them at the surface
+fn update_swimming(
+    waters: Query<&ColliderAabb, With<WaterVolume>>,
+    mut player_query: Query<(&Transform, &mut Player, &mut LinearVelocity)>,
+    time: Res<Time>,
+) {
+    let Ok((transform, mut player, mut velocity)) = player_query.get_single_mut() else {
+        return;
+    };
+    let chest = transform.translation + Vec3::Y * SWIM_DEPTH;
+    // Some slack above the surface once swimming, so bobbing doesn't flicker in and out of it
+    let surface_slack = if player.is_swimming { 0.3 } else { 0.0 };
+
+    let surface = waters.iter().find_map(|aabb| {
+        let inside = chest.x >= aabb.min.x
+            && chest.x <= aabb.max.x
+            && chest.z >= aabb.min.z
+            && chest.z <= aabb.max.z
+            && chest.y >= aabb.min.y
+            && chest.y <= aabb.max.y + surface_slack;
+        inside.then_some(aabb.max.y)
+    });
+
+    let Some(surface) = surface else {
+        if player.is_swimming {
+            player.is_swimming = false;
+            info!("Player left the water");
+        }
+        return;
+    };
+    if !player.is_swimming {
+        player.is_swimming = true;
+        info!("Player is swimming");
+    }
+
+    // Float with the chest at the surface instead of sinking to the bottom
+    let depth = surface - chest.y;
+    let target_vertical_speed = depth * SURFACE_PULL;
+    velocity.y = velocity.y.lerp(target_vertical_speed, (SURFACE_PULL * time.delta_secs()).min(1.0));
+}
@@ -0,0 +1,380 @@
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::input::{Action, ActionState};
+use crate::lighting::{update_sky, Sun};
+use crate::world::LevelManifest;
+
+// Seconds it takes to blend from one weather to the next
+const TRANSITION_TIME: f32 = 20.0;
+// Size of the box above the camera that rain and snow fall from
+const PRECIPITATION_AREA: Vec3 = Vec3::new(40.0, 0.0, 40.0);
+const PRECIPITATION_HEIGHT: f32 = 15.0;
+// Sky color heavy clouds blend the clear color towards
+const CLOUD_COLOR: Color = Color::srgb(0.35, 0.37, 0.4);
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<HanabiPlugin>() {
+            app.add_plugins(HanabiPlugin);
+        }
+        app.init_resource::<Weather>()
+            .add_systems(Startup, setup_precipitation_effects)
+            .add_systems(Update, (
+                apply_level_weather.run_if(resource_changed::<LevelManifest>),
+                weather_controls,
+                advance_weather,
+                update_precipitation,
+                apply_weather.after(update_sky),
+            ).chain());
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WeatherKind {
+    #[default]
+    Clear,
+    Overcast,
+    Rain,
+    Snow,
+    HeavyFog,
+}
+
+// What a weather does to the world, blended during transitions
+#[derive(Debug, Clone, Copy)]
+struct WeatherParams {
+    fog_density: f32,
+    // Multiplies the sun and moon illuminance
+    light_factor: f32,
+    // How much the sky is covered, 0 keeps the time of day's clear color
+    cloudiness: f32,
+    // How wet surfaces are, see Weather::wetness
+    wetness: f32,
+}
+
+impl WeatherKind {
+    fn params(self) -> WeatherParams {
+        match self {
+            WeatherKind::Clear => WeatherParams { fog_density: 0.05, light_factor: 1.0, cloudiness: 0.0, wetness: 0.0 },
+            WeatherKind::Overcast => WeatherParams { fog_density: 0.07, light_factor: 0.5, cloudiness: 0.7, wetness: 0.0 },
+            WeatherKind::Rain => WeatherParams { fog_density: 0.09, light_factor: 0.35, cloudiness: 0.85, wetness: 1.0 },
+            WeatherKind::Snow => WeatherParams { fog_density: 0.1, light_factor: 0.6, cloudiness: 0.6, wetness: 0.4 },
+            WeatherKind::HeavyFog => WeatherParams { fog_density: 0.25, light_factor: 0.4, cloudiness: 0.5, wetness: 0.2 },
+        }
+    }
+}
+
+fn lerp_params(from: WeatherParams, to: WeatherParams, t: f32) -> WeatherParams {
+    WeatherParams {
+        fog_density: from.fog_density.lerp(to.fog_density, t),
+        light_factor: from.light_factor.lerp(to.light_factor, t),
+        cloudiness: from.cloudiness.lerp(to.cloudiness, t),
+        wetness: from.wetness.lerp(to.wetness, t),
+    }
+}
+
+/// Weather of a level, set in its manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelWeather {
+    #[serde(default)]
+    pub initial: WeatherKind,
+    // Weathers the level changes between, it keeps the initial one when empty
+    #[serde(default)]
+    pub cycle: Vec<WeatherKind>,
+    // Seconds a weather lasts before the next one is picked
+    #[serde(default = "default_min_duration")]
+    pub min_duration: f32,
+    #[serde(default = "default_max_duration")]
+    pub max_duration: f32,
+}
+
+fn default_min_duration() -> f32 {
+    120.0
+}
+
+fn default_max_duration() -> f32 {
+    300.0
+}
+
+impl Default for LevelWeather {
+    fn default() -> Self {
+        Self {
+            initial: WeatherKind::Clear,
+            cycle: Vec::new(),
+            min_duration: default_min_duration(),
+            max_duration: default_max_duration(),
+        }
+    }
+}
+
+// Current weather, gameplay code reads wetness() or current() from here
+#[derive(Resource)]
+pub struct Weather {
+    current: WeatherKind,
+    // Weather we're blending towards, the same as current when settled
+    target: WeatherKind,
+    blend: f32,
+    // Time left until the next weather is picked
+    timer: f32,
+    settings: LevelWeather,
+    rng: u64,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            current: WeatherKind::Clear,
+            target: WeatherKind::Clear,
+            blend: 1.0,
+            timer: default_max_duration(),
+            settings: LevelWeather::default(),
+            rng: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+}
+
+impl Weather {
+    // The weather that's taking over, or the settled one
+    pub fn current(&self) -> WeatherKind {
+        if self.blend >= 0.5 { self.target } else { self.current }
+    }
+
+    // 0 for dry ground, 1 in full rain
+    pub fn wetness(&self) -> f32 {
+        self.params().wetness
+    }
+
+    // How much stamina regenerates compared to dry weather
+    pub fn stamina_regen_factor(&self) -> f32 {
+        1.0 - self.wetness() * 0.3
+    }
+
+    // How well the player grips the ground, wet ground makes them slide
+    pub fn traction(&self) -> f32 {
+        1.0 - self.wetness() * 0.4
+    }
+
+    // Start blending towards another weather
+    pub fn set(&mut self, kind: WeatherKind) {
+        if kind == self.target {
+            return;
+        }
+        info!("Weather changing to {:?}", kind);
+        self.current = self.current();
+        self.target = kind;
+        self.blend = 0.0;
+        self.timer = self.next_duration();
+    }
+
+    fn params(&self) -> WeatherParams {
+        lerp_params(self.current.params(), self.target.params(), self.blend)
+    }
+
+    // xorshift, good enough for picking weathers
+    fn next_random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn next_duration(&mut self) -> f32 {
+        let t = self.next_random();
+        self.settings.min_duration.lerp(self.settings.max_duration.max(self.settings.min_duration), t)
+    }
+}
+
+// Rain or snow emitter, kept above the camera
+#[derive(Component)]
+pub struct Precipitation(pub WeatherKind);
+
+#[derive(Resource)]
+struct PrecipitationEffects {
+    rain: Handle<EffectAsset>,
+    snow: Handle<EffectAsset>,
+}
+
+fn precipitation_effect(name: &str, rate: f32, lifetime: f32, velocity: Vec3, size: Vec3, color: Vec4) -> EffectAsset {
+    let writer = ExprWriter::new();
+
+    // Random point in a flat box above the emitter
+    let half_area = writer.lit(PRECIPITATION_AREA * 0.5);
+    let offset = writer.lit(Vec3::Y * PRECIPITATION_HEIGHT);
+    let position = (writer.rand(VectorType::VEC3F) * writer.lit(Vec3::splat(2.0)) - writer.lit(Vec3::ONE)) * half_area + offset;
+    let init_position = SetAttributeModifier::new(Attribute::POSITION, position.expr());
+    let init_velocity = SetAttributeModifier::new(Attribute::VELOCITY, writer.lit(velocity).expr());
+    let init_age = SetAttributeModifier::new(Attribute::AGE, writer.lit(0.0).expr());
+    let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, writer.lit(lifetime).expr());
+
+    EffectAsset::new(32768, Spawner::rate(rate.into()), writer.finish())
+        .with_name(name)
+        // Drops keep falling where they were emitted when the camera moves on
+        .with_simulation_space(SimulationSpace::Global)
+        .init(init_position)
+        .init(init_velocity)
+        .init(init_age)
+        .init(init_lifetime)
+        .render(OrientModifier::new(OrientMode::AlongVelocity))
+        .render(SetColorModifier { color: color.into() })
+        .render(SetSizeModifier { size: size.into() })
+}
+
+fn setup_precipitation_effects(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+) {
+    let rain = precipitation_effect(
+        "rain",
+        4000.0,
+        1.0,
+        Vec3::new(0.0, -18.0, 0.0),
+        Vec3::new(0.02, 0.4, 0.02),
+        Vec4::new(0.6, 0.65, 0.8, 0.5),
+    );
+    let snow = precipitation_effect(
+        "snow",
+        1500.0,
+        8.0,
+        Vec3::new(0.3, -2.0, 0.1),
+        Vec3::splat(0.06),
+        Vec4::new(1.0, 1.0, 1.0, 0.9),
+    );
+    commands.insert_resource(PrecipitationEffects {
+        rain: effects.add(rain),
+        snow: effects.add(snow),
+    });
+}
+
+// Each level starts with its own weather and cycles through its own list
+fn apply_level_weather(
+    manifest: Res<LevelManifest>,
+    mut weather: ResMut<Weather>,
+) {
+    let settings = manifest.weather.clone().unwrap_or_default();
+    weather.current = settings.initial;
+    weather.target = settings.initial;
+    weather.blend = 1.0;
+    weather.settings = settings;
+    weather.timer = weather.next_duration();
+}
+
+// Debug key: F5 cycles through the weathers
+fn weather_controls(
+    actions: Res<ActionState>,
+    mut weather: ResMut<Weather>,
+) {
+    if !actions.just_pressed(Action::DebugCycleWeather) {
+        return;
+    }
+    let next = match weather.target {
+        WeatherKind::Clear => WeatherKind::Overcast,
+        WeatherKind::Overcast => WeatherKind::Rain,
+        WeatherKind::Rain => WeatherKind::Snow,
+        WeatherKind::Snow => WeatherKind::HeavyFog,
+        WeatherKind::HeavyFog => WeatherKind::Clear,
+    };
+    weather.set(next);
+}
+
+fn advance_weather(
+    mut weather: ResMut<Weather>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    if weather.blend < 1.0 {
+        weather.blend = (weather.blend + dt / TRANSITION_TIME).min(1.0);
+        if weather.blend >= 1.0 {
+            weather.current = weather.target;
+        }
+        return;
+    }
+
+    if weather.settings.cycle.is_empty() {
+        return;
+    }
+    weather.timer -= dt;
+    if weather.timer > 0.0 {
+        return;
+    }
+    let index = (weather.next_random() * weather.settings.cycle.len() as f32) as usize;
+    let next = weather.settings.cycle[index.min(weather.settings.cycle.len() - 1)];
+    if next == weather.target {
+        weather.timer = weather.next_duration();
+    } else {
+        weather.set(next);
+    }
+}
+
+// Keep one rain or snow emitter above the camera, swapped when the weather changes over
+fn update_precipitation(
+    mut commands: Commands,
+    weather: Res<Weather>,
+    effects: Option<Res<PrecipitationEffects>>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut emitters: Query<(Entity, &Precipitation, &mut Transform)>,
+) {
+    let Some(effects) = effects else {
+        return;
+    };
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let wanted = match weather.current() {
+        kind @ (WeatherKind::Rain | WeatherKind::Snow) => Some(kind),
+        _ => None,
+    };
+
+    let mut has_wanted = false;
+    for (entity, precipitation, mut transform) in &mut emitters {
+        if Some(precipitation.0) == wanted {
+            transform.translation = camera.translation();
+            has_wanted = true;
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let Some(kind) = wanted else {
+        return;
+    };
+    if has_wanted {
+        return;
+    }
+    let effect = if kind == WeatherKind::Rain { effects.rain.clone() } else { effects.snow.clone() };
+    commands.spawn((
+        Name::new(format!("{:?} emitter", kind)),
+        ParticleEffect::new(effect),
+        Transform::from_translation(camera.translation()),
+        Precipitation(kind),
+    ));
+}
+
+// Runs after the day/night cycle set the sky and sun for the hour, and darkens them for the weather
+fn apply_weather(
+    weather: Res<Weather>,
+    mut clear_color: ResMut<ClearColor>,
+    mut fogs: Query<&mut DistanceFog>,
+    mut suns: Query<&mut DirectionalLight, With<Sun>>,
+) {
+    let params = weather.params();
+
+    for mut fog in &mut fogs {
+        fog.falloff = FogFalloff::Exponential {
+            density: params.fog_density,
+        };
+    }
+    for mut light in &mut suns {
+        light.illuminance *= params.light_factor;
+    }
+
+    // Clouds take the color out of the sky but keep it as bright as the time of day made it
+    let sky = LinearRgba::from(clear_color.0);
+    let brightness = (sky.red + sky.green + sky.blue) / 3.0;
+    let cloud = LinearRgba::from(CLOUD_COLOR);
+    let scale = brightness / ((cloud.red + cloud.green + cloud.blue) / 3.0);
+    let clouds = LinearRgba::rgb(cloud.red * scale, cloud.green * scale, cloud.blue * scale);
+    clear_color.0 = sky.mix(&clouds, params.cloudiness).into();
+}
@@ -1,68 +1,538 @@
-use avian3d::prelude::{Collider, RigidBody};
-use bevy::prelude::*;
+use avian3d::prelude::{Collider, LinearVelocity, RigidBody, Sensor};
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use crate::physics::on_level_spawn;
+use crate::dungeon::{spawn_dungeon, DungeonConfig};
+use crate::interactables::insert_interactable;
+use crate::physics::{
+    on_level_spawn, BInteractable, BMarker, BRigidBody, BTrigger, GameLayer, LevelExtrasError, LevelLoadReport, LevelMarker,
+    LevelSpawned, ReloadedLevel, SpawnedByLevel,
+};
+use crate::player::{FallDamage, Player};
+use crate::scatter::{ScatterLayer, ScatterSurface};
+use crate::terrain::{spawn_terrain, TerrainConfig};
+use crate::triggers::TriggerVolume;
+use crate::weather::LevelWeather;
 
+// Level loaded when none is given with --level <name>
+const DEFAULT_LEVEL: &str = "playground";
+// Relative to the asset folder
+const LEVELS_DIR: &str = "levels";
 
-// Scene creation system with physics
-pub fn spawn_scene(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
+// Which manifest in assets/levels to load, picked on the command line
+#[derive(Resource, Debug, Clone)]
+pub struct LevelSelection {
+    pub name: String,
+}
+
+impl LevelSelection {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let name = args
+            .iter()
+            .position(|arg| arg == "--level")
+            .and_then(|index| args.get(index + 1))
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_LEVEL.to_string());
+        Self { name }
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        manifest_path(&self.name)
+    }
+}
+
+// Resolved the same way the AssetServer finds the asset folder (BEVY_ASSET_ROOT, then
+// CARGO_MANIFEST_DIR, then next to the executable), so it doesn't depend on the working directory
+fn levels_dir() -> PathBuf {
+    FileAssetReader::get_base_path().join("assets").join(LEVELS_DIR)
+}
+
+fn manifest_path(level: &str) -> PathBuf {
+    levels_dir().join(format!("{}.json", level))
+}
+
+// Every level that ships in assets/levels, portals can only lead to these
+#[derive(Resource, Debug, Default)]
+pub struct LevelRegistry {
+    pub levels: Vec<String>,
+}
+
+impl LevelRegistry {
+    pub fn scan() -> Self {
+        let dir = levels_dir();
+        let mut levels: Vec<String> = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+                .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+                .collect(),
+            Err(err) => {
+                warn!("Can't list levels in {}: {}", dir.display(), err);
+                Vec::new()
+            }
+        };
+        levels.sort();
+        Self { levels }
+    }
+
+    pub fn contains(&self, level: &str) -> bool {
+        self.levels.iter().any(|name| name == level)
+    }
+
+    pub fn load(&self, level: &str) -> Result<LevelManifest, String> {
+        if !self.contains(level) {
+            return Err(format!("no level named {} in {}", level, levels_dir().display()));
+        }
+        LevelManifest::load(&manifest_path(level))
+    }
+}
+
+/// Everything a level is made of, loaded from assets/levels/<name>.json
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct LevelManifest {
+    pub name: String,
+    // Sky at night, days blend it towards lighting::DAY_SKY_COLOR
+    #[serde(default = "default_sky_color")]
+    pub sky_color: [f32; 3],
+    #[serde(default)]
+    pub lighting: LevelLighting,
+    // Clear skies all the time when omitted
+    pub weather: Option<LevelWeather>,
+    // Materials the primitives refer to by name
+    #[serde(default)]
+    pub materials: HashMap<String, LevelMaterial>,
+    #[serde(default)]
+    pub primitives: Vec<LevelPrimitive>,
+    // Generated hills, usually instead of a Plane primitive
+    pub terrain: Option<TerrainConfig>,
+    // Foliage and props spread over the surfaces, see scatter.rs
+    #[serde(default)]
+    pub scatter: Vec<ScatterLayer>,
+    // glb scenes, their physics comes from the gltf extras, see on_level_spawn
+    #[serde(default)]
+    pub scenes: Vec<LevelSceneEntry>,
+    // Procedurally generated dungeons, see dungeon.rs
+    #[serde(default)]
+    pub dungeons: Vec<DungeonConfig>,
+    // Player starts and portal destinations of levels without a glb
+    #[serde(default)]
+    pub markers: Vec<LevelMarkerEntry>,
+}
+
+fn default_sky_color() -> [f32; 3] {
+    [0.05, 0.08, 0.15]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelLighting {
+    // Strength and color of the sun at noon
+    #[serde(default = "default_sun_illuminance")]
+    pub sun_illuminance: f32,
+    #[serde(default = "default_white")]
+    pub sun_color: [f32; 3],
+    // Euler angles in degrees. Only the yaw is used, it's where the sun stands at noon and the
+    // day/night cycle moves it from there
+    #[serde(default = "default_sun_rotation")]
+    pub sun_rotation: Vec3,
+    #[serde(default = "default_true")]
+    pub shadows: bool,
+    #[serde(default = "default_white")]
+    pub ambient_color: [f32; 3],
+    #[serde(default = "default_ambient_brightness")]
+    pub ambient_brightness: f32,
+    // Hour of the day (0-24) the level starts at, keeps the current time when omitted
+    pub start_hour: Option<f32>,
+}
+
+impl Default for LevelLighting {
+    fn default() -> Self {
+        Self {
+            sun_illuminance: default_sun_illuminance(),
+            sun_color: default_white(),
+            sun_rotation: default_sun_rotation(),
+            shadows: true,
+            ambient_color: default_white(),
+            ambient_brightness: default_ambient_brightness(),
+            start_hour: None,
+        }
+    }
+}
+
+fn default_sun_illuminance() -> f32 {
+    15000.0
+}
+
+fn default_sun_rotation() -> Vec3 {
+    Vec3::new(-45.0, 45.0, 0.0)
+}
+
+fn default_ambient_brightness() -> f32 {
+    80.0
+}
+
+fn default_white() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelMaterial {
+    #[serde(default = "default_white")]
+    pub color: [f32; 3],
+    #[serde(default)]
+    pub metallic: f32,
+    #[serde(default = "default_roughness")]
+    pub roughness: f32,
+    #[serde(default = "default_reflectance")]
+    pub reflectance: f32,
+    pub emissive: Option<[f32; 3]>,
+}
+
+fn default_roughness() -> f32 {
+    0.5
+}
+
+fn default_reflectance() -> f32 {
+    0.5
+}
+
+impl From<&LevelMaterial> for StandardMaterial {
+    fn from(material: &LevelMaterial) -> Self {
+        let [r, g, b] = material.color;
+        StandardMaterial {
+            base_color: Color::srgb(r, g, b),
+            metallic: material.metallic,
+            perceptual_roughness: material.roughness,
+            reflectance: material.reflectance,
+            emissive: material
+                .emissive
+                .map_or(LinearRgba::BLACK, |[r, g, b]| LinearRgba::rgb(r, g, b)),
+            ..default()
+        }
+    }
+}
+
+// Serde friendly transform, rotation is in degrees
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelTransform {
+    #[serde(default)]
+    pub translation: Vec3,
+    #[serde(default)]
+    pub rotation: Vec3,
+    #[serde(default = "default_scale")]
+    pub scale: Vec3,
+}
+
+impl Default for LevelTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: default_scale(),
+        }
+    }
+}
+
+fn default_scale() -> Vec3 {
+    Vec3::ONE
+}
+
+impl From<&LevelTransform> for Transform {
+    fn from(transform: &LevelTransform) -> Self {
+        Transform {
+            translation: transform.translation,
+            rotation: Quat::from_euler(
+                EulerRot::XYZ,
+                transform.rotation.x.to_radians(),
+                transform.rotation.y.to_radians(),
+                transform.rotation.z.to_radians(),
+            ),
+            scale: transform.scale,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LevelShape {
+    // Infinite ground, the size is only for the mesh
+    Plane { size: [f32; 2] },
+    Cuboid { size: Vec3 },
+    Sphere { radius: f32 },
+    Capsule { radius: f32, height: f32 },
+    Cylinder { radius: f32, height: f32 },
+}
+
+impl LevelShape {
+    pub fn mesh(&self) -> Mesh {
+        match self {
+            LevelShape::Plane { size } => Plane3d::default().mesh().size(size[0], size[1]).into(),
+            LevelShape::Cuboid { size } => Cuboid::new(size.x, size.y, size.z).into(),
+            LevelShape::Sphere { radius } => Sphere::new(*radius).into(),
+            LevelShape::Capsule { radius, height } => Capsule3d::new(*radius, *height).into(),
+            LevelShape::Cylinder { radius, height } => Cylinder::new(*radius, *height).into(),
+        }
+    }
+
+    pub fn collider(&self) -> Collider {
+        match self {
+            LevelShape::Plane { .. } => Collider::half_space(Vec3::Y),
+            LevelShape::Cuboid { size } => Collider::cuboid(size.x, size.y, size.z),
+            LevelShape::Sphere { radius } => Collider::sphere(*radius),
+            LevelShape::Capsule { radius, height } => Collider::capsule(*radius, *height),
+            LevelShape::Cylinder { radius, height } => Collider::cylinder(*radius, *height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelPrimitive {
+    pub shape: LevelShape,
+    pub material: Option<String>,
+    #[serde(default)]
+    pub transform: LevelTransform,
+    // No body means the primitive is only for show
+    pub rigid_body: Option<BRigidBody>,
+    // Turns the primitive into a sensor volume like BMeshExtras::trigger
+    pub trigger: Option<BTrigger>,
+    // Scatter layers with this surface tag place things on the primitive
+    pub scatter_surface: Option<String>,
+    // Door, lever or chest like BMeshExtras::interactable
+    pub interactable: Option<BInteractable>,
+    // Defaults to GameLayer::trigger() for triggers, GameLayer::prop() for dynamic bodies
+    // and GameLayer::environment() otherwise
+    pub layers: Option<Vec<GameLayer>>,
+    pub collides_with: Option<Vec<GameLayer>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelMarkerEntry {
+    pub marker: BMarker,
+    #[serde(default)]
+    pub transform: LevelTransform,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelSceneEntry {
+    // e.g. "models/playground.glb#Scene0"
+    pub path: String,
+    #[serde(default)]
+    pub transform: LevelTransform,
+}
+
+impl LevelManifest {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+        let deserializer = &mut serde_json::Deserializer::from_str(&text);
+        serde_path_to_error::deserialize(deserializer)
+            .map_err(|err| format!("{} at {}: {}", path.display(), err.path(), err.inner()))
+    }
+
+    // Bare ground so the game still starts when a manifest is missing or broken
+    pub fn fallback() -> Self {
+        Self {
+            name: "Fallback".to_string(),
+            sky_color: default_sky_color(),
+            lighting: LevelLighting::default(),
+            weather: None,
+            materials: HashMap::new(),
+            terrain: None,
+            scatter: Vec::new(),
+            primitives: vec![LevelPrimitive {
+                shape: LevelShape::Plane { size: [100.0, 100.0] },
+                material: None,
+                transform: LevelTransform::default(),
+                rigid_body: Some(BRigidBody::Static),
+                trigger: None,
+                scatter_surface: None,
+                interactable: None,
+                layers: None,
+                collides_with: None,
+            }],
+            scenes: Vec::new(),
+            dungeons: Vec::new(),
+            markers: Vec::new(),
+        }
+    }
+}
+
+// Root of a level glb instance, respawned when the file changes on disk
+#[derive(Component)]
+pub struct LevelScene;
+
+// Top level entity spawned from the current level manifest, despawned by unload_level
+#[derive(Component)]
+pub struct LevelEntity;
+
+// Where the player stood when a level reload started
+#[derive(Resource, Default)]
+struct PendingLevelReload {
+    player_transform: Option<Transform>,
+}
+
+fn spawn_level_scene(commands: &mut Commands, scene: Handle<Scene>, transform: Transform) -> Entity {
+    commands
+        .spawn((SceneRoot(scene), transform, LevelScene, LevelEntity))
+        .observe(on_level_spawn)
+        .id()
+}
+
+// Spawn the primitives, scenes and dungeons of a level manifest
+pub fn spawn_level(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    asset_server: &AssetServer,
+    manifest: &LevelManifest,
 ) {
-    // ==============================================
-    // Create the ground
-    // ==============================================
-    commands.spawn((
-        Mesh3d(meshes.add(Plane3d::default().mesh().size(100.0, 100.0))),
-        MeshMaterial3d(materials.add(Color::WHITE)),
-        RigidBody::Static,
-        Collider::half_space(Vec3::Y),
-    ));
-   
-
-    // dynamic spheres
-    let sphere_mesh = meshes.add(Sphere::new(0.8));
-    
-    let chrome_material = materials.add(StandardMaterial {
-        base_color: Color::srgb(0.8, 0.8, 0.9),
-        metallic: 1.0,
-        perceptual_roughness: 0.1,
-        reflectance: 1.0,
-        ..default()
-    });
-    
-    for i in 0..8 {
-        let angle = i as f32 * std::f32::consts::PI * 2.0 / 8.0;
-        let distance = 15.0;
-        let x = angle.sin() * distance;
-        let z = angle.cos() * distance;
-        
+    info!("Spawning level {}", manifest.name);
+
+    let level_materials: HashMap<&str, Handle<StandardMaterial>> = manifest
+        .materials
+        .iter()
+        .map(|(name, material)| (name.as_str(), materials.add(StandardMaterial::from(material))))
+        .collect();
+    let fallback_material = materials.add(Color::WHITE);
+    let level_material = |name: &Option<String>| match name {
+        Some(name) => level_materials.get(name.as_str()).cloned().unwrap_or_else(|| {
+            warn!("Level {} has no material named {}", manifest.name, name);
+            fallback_material.clone()
+        }),
+        None => fallback_material.clone(),
+    };
+
+    // Parts of the manifest that are skipped, reported like bad glb extras
+    let mut report = LevelLoadReport::default();
+    let mut manifest_error = |node: String, extras: String, (path, message): (String, String)| {
+        report.push_error(LevelExtrasError {
+            node: format!("{}: {}", manifest.name, node),
+            extras,
+            path,
+            message,
+        });
+    };
+
+    if let Some(terrain) = &manifest.terrain {
+        match terrain.validate() {
+            Ok(()) => {
+                let terrain = spawn_terrain(commands, meshes, level_material(&terrain.material), terrain);
+                commands.entity(terrain).insert(LevelEntity);
+            }
+            Err(err) => manifest_error("terrain".to_string(), serde_json::to_string(terrain).unwrap_or_default(), err),
+        }
+    }
+
+    for (index, primitive) in manifest.primitives.iter().enumerate() {
+        let mut entity = commands.spawn((
+            Mesh3d(meshes.add(primitive.shape.mesh())),
+            MeshMaterial3d(level_material(&primitive.material)),
+            Transform::from(&primitive.transform),
+            LevelEntity,
+        ));
+        if let Some(surface) = &primitive.scatter_surface {
+            entity.insert(ScatterSurface(surface.clone()));
+        }
+        if let Some(interactable) = &primitive.interactable {
+            match interactable.validate() {
+                Ok(()) => insert_interactable(&mut entity, interactable),
+                Err((path, message)) => manifest_error(
+                    format!("primitives[{}]", index),
+                    serde_json::to_string(interactable).unwrap_or_default(),
+                    (path, message),
+                ),
+            }
+        }
+
+        if primitive.rigid_body.is_none() && primitive.trigger.is_none() {
+            continue;
+        }
+        let default_layers = match (&primitive.trigger, &primitive.rigid_body) {
+            (Some(_), _) => GameLayer::trigger(),
+            (None, Some(BRigidBody::Dynamic)) => GameLayer::prop(),
+            _ => GameLayer::environment(),
+        };
+        entity.insert((
+            primitive.shape.collider(),
+            GameLayer::with_overrides(
+                default_layers,
+                primitive.layers.as_deref(),
+                primitive.collides_with.as_deref(),
+            ),
+        ));
+        if let Some(rigid_body) = &primitive.rigid_body {
+            entity.insert(RigidBody::from(rigid_body));
+        }
+        if let Some(kind) = &primitive.trigger {
+            entity.insert((
+                Sensor,
+                TriggerVolume {
+                    kind: kind.clone(),
+                    player_inside: false,
+                },
+            ));
+        }
+    }
+
+    for scene in &manifest.scenes {
+        spawn_level_scene(commands, asset_server.load(&scene.path), Transform::from(&scene.transform));
+    }
+
+    for (index, dungeon) in manifest.dungeons.iter().enumerate() {
+        if let Err(err) = dungeon.validate() {
+            manifest_error(format!("dungeons[{}]", index), serde_json::to_string(dungeon).unwrap_or_default(), err);
+            continue;
+        }
+        if let Some(dungeon) = spawn_dungeon(commands, meshes, materials, dungeon) {
+            commands.entity(dungeon).insert(LevelEntity);
+        }
+    }
+
+    for marker in &manifest.markers {
         commands.spawn((
-            Mesh3d(sphere_mesh.clone()),
-            MeshMaterial3d(chrome_material.clone()),
-            Transform::from_xyz(x, 1.0, z),
-            RigidBody::Dynamic,
-            Collider::sphere(0.8),
+            LevelMarker(marker.marker.clone()),
+            Transform::from(&marker.transform),
+            LevelEntity,
         ));
     }
 
-    commands
-        .spawn(SceneRoot(
-            asset_server.load(
-                GltfAssetLabel::Scene(0)
-                    .from_asset("models/playground.glb"),
-            ),
-        ))
-        .observe(on_level_spawn);
+    if !report.is_ok() {
+        warn!("Level {} skipped {} bad manifest entries", manifest.name, report.errors.len());
+        commands.insert_resource(report.clone());
+        commands.send_event(report);
+    }
+}
+
+// Despawn everything the current level spawned, including what gameplay code put at its markers
+pub fn unload_level(
+    commands: &mut Commands,
+    level_entities: &Query<Entity, With<LevelEntity>>,
+    level_owned: &Query<(Entity, &SpawnedByLevel)>,
+) {
+    for entity in level_entities.iter().chain(level_owned.iter().map(|(entity, _)| entity)) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_scene(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+    manifest: Res<LevelManifest>,
+) {
+    spawn_level(&mut commands, &mut meshes, &mut materials, &asset_server, &manifest);
 
 
     /*
     // Testing some assets
     commands.spawn(SceneRoot(asset_server.load(
         "models/girly.glb#Scene0")));
-
     commands.spawn((
         SceneRoot(asset_server.load(
         "models/huge_icelandic_lava_cliff_sieoz_high.glb#Scene0")),
@@ -72,13 +542,78 @@ pub fn spawn_scene(
 }
 
 
+// When an artist re-exports a level glb, throw away the old instance and spawn the new one,
+// on_level_spawn then runs again on the fresh entities
+fn hot_reload_level(
+    mut commands: Commands,
+    mut scene_events: EventReader<AssetEvent<Scene>>,
+    levels: Query<(Entity, &SceneRoot, &Transform), With<LevelScene>>,
+    level_owned: Query<(Entity, &SpawnedByLevel)>,
+    player_query: Query<&Transform, With<Player>>,
+    mut pending_reload: ResMut<PendingLevelReload>,
+) {
+    for event in scene_events.read() {
+        for (level, scene_root, transform) in &levels {
+            if !event.is_modified(&scene_root.0) {
+                continue;
+            }
+            info!("Level scene changed on disk, reloading");
+
+            for (entity, spawned_by) in &level_owned {
+                if spawned_by.0 == level {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            commands.entity(level).despawn_recursive();
+
+            let new_level = spawn_level_scene(&mut commands, scene_root.0.clone(), *transform);
+            commands.entity(new_level).insert(ReloadedLevel);
+
+            if let Ok(player_transform) = player_query.get_single() {
+                pending_reload.player_transform = Some(*player_transform);
+            }
+        }
+    }
+}
+
+// Put the player back where they were, in case they dropped while the level was gone
+fn restore_player_after_reload(
+    mut level_events: EventReader<LevelSpawned>,
+    mut pending_reload: ResMut<PendingLevelReload>,
+    mut player_query: Query<(&mut Transform, &mut LinearVelocity, &mut FallDamage), With<Player>>,
+) {
+    if !level_events.read().any(|event| event.reloaded) {
+        return;
+    }
+    let Some(saved_transform) = pending_reload.player_transform.take() else {
+        return;
+    };
+    if let Ok((mut transform, mut velocity, mut fall)) = player_query.get_single_mut() {
+        *transform = saved_transform;
+        velocity.0 = Vec3::ZERO;
+        fall.reset();
+    }
+}
+
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
+            let selection = LevelSelection::from_args();
+            let manifest = LevelManifest::load(&selection.manifest_path()).unwrap_or_else(|err| {
+                error!("Failed to load level manifest {}", err);
+                LevelManifest::fallback()
+            });
+
             app
-            // Set a dark sky color
-            .insert_resource(ClearColor(Color::srgb(0.05, 0.08, 0.15)))
-            .add_systems(Startup, spawn_scene);
+            .insert_resource(selection)
+            .insert_resource(LevelRegistry::scan())
+            .insert_resource(manifest)
+            .init_resource::<PendingLevelReload>()
+            .add_systems(Startup, spawn_scene)
+            .add_systems(Update, (
+                hot_reload_level,
+                restore_player_after_reload,
+            ));
     }
 }
\ No newline at end of file